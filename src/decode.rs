@@ -0,0 +1,760 @@
+use std::fmt;
+
+use crate::Byte;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mnemonic {
+    Adc,
+    And,
+    Asl,
+    Bcc,
+    Bcs,
+    Beq,
+    Bit,
+    Bmi,
+    Bne,
+    Bpl,
+    Brk,
+    Bvc,
+    Bvs,
+    Clc,
+    Cld,
+    Cli,
+    Clv,
+    Cmp,
+    Cpx,
+    Cpy,
+    Dec,
+    Dex,
+    Dey,
+    Eor,
+    Inc,
+    Inx,
+    Iny,
+    Jmp,
+    Jsr,
+    Lda,
+    Ldx,
+    Ldy,
+    Lsr,
+    Nop,
+    Ora,
+    Pha,
+    Php,
+    Pla,
+    Plp,
+    Rol,
+    Ror,
+    Rti,
+    Rts,
+    Sbc,
+    Sec,
+    Sed,
+    Sei,
+    Sta,
+    Stx,
+    Sty,
+    Tax,
+    Tay,
+    Tsx,
+    Txa,
+    Txs,
+    Tya,
+    // stable undocumented NMOS instructions
+    Alr,
+    Anc,
+    Arr,
+    Dcp,
+    Isc,
+    Lax,
+    Rla,
+    Rra,
+    Sax,
+    Sbx,
+    Slo,
+    Sre,
+    // unstable undocumented NMOS instructions
+    Ane,
+    Las,
+    Lxa,
+    Sha,
+    Shx,
+    Shy,
+    Tas,
+    // locks up the CPU until reset
+    Jam,
+    // 65C02 additions
+    Bra,
+    Phx,
+    Phy,
+    Plx,
+    Ply,
+    Stz,
+    Trb,
+    Tsb,
+    // Rockwell and WDC bit instructions, the digit is the bit they work on
+    Rmb0,
+    Rmb1,
+    Rmb2,
+    Rmb3,
+    Rmb4,
+    Rmb5,
+    Rmb6,
+    Rmb7,
+    Smb0,
+    Smb1,
+    Smb2,
+    Smb3,
+    Smb4,
+    Smb5,
+    Smb6,
+    Smb7,
+    Bbr0,
+    Bbr1,
+    Bbr2,
+    Bbr3,
+    Bbr4,
+    Bbr5,
+    Bbr6,
+    Bbr7,
+    Bbs0,
+    Bbs1,
+    Bbs2,
+    Bbs3,
+    Bbs4,
+    Bbs5,
+    Bbs6,
+    Bbs7,
+    // WDC wait for interrupt and stop the clock
+    Wai,
+    Stp,
+}
+
+// the three letters an assembler uses, e.g. "LDA"
+impl fmt::Display for Mnemonic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = format!("{self:?}").to_uppercase();
+        f.pad(&name)
+    }
+}
+
+impl Mnemonic {
+    // the bit RMB, SMB, BBR and BBS test or change, None for everything else
+    pub fn bit(self) -> Option<Byte> {
+        use Mnemonic::*;
+        match self {
+            Rmb0 | Smb0 | Bbr0 | Bbs0 => Some(0),
+            Rmb1 | Smb1 | Bbr1 | Bbs1 => Some(1),
+            Rmb2 | Smb2 | Bbr2 | Bbs2 => Some(2),
+            Rmb3 | Smb3 | Bbr3 | Bbs3 => Some(3),
+            Rmb4 | Smb4 | Bbr4 | Bbs4 => Some(4),
+            Rmb5 | Smb5 | Bbr5 | Bbs5 => Some(5),
+            Rmb6 | Smb6 | Bbr6 | Bbs6 => Some(6),
+            Rmb7 | Smb7 | Bbr7 | Bbs7 => Some(7),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressingMode {
+    Implied,
+    Accumulator,
+    Immediate,
+    ZeroPage,
+    ZeroPageX,
+    ZeroPageY,
+    Absolute,
+    AbsoluteX,
+    AbsoluteY,
+    // only JMP ($nnnn)
+    Indirect,
+    IndirectX,
+    IndirectY,
+    // 65C02 ($nn), (ind),Y without the index
+    ZeroPageIndirect,
+    // 65C02 JMP ($nnnn,X)
+    AbsoluteIndexedIndirect,
+    // branches, a signed offset from the next instruction
+    Relative,
+    // BBR and BBS, a zero page address then a branch offset
+    ZeroPageRelative,
+}
+
+impl AddressingMode {
+    // how many bytes follow the opcode
+    pub fn operand_bytes(self) -> u16 {
+        match self {
+            AddressingMode::Implied | AddressingMode::Accumulator => 0,
+            AddressingMode::Immediate
+            | AddressingMode::ZeroPage
+            | AddressingMode::ZeroPageX
+            | AddressingMode::ZeroPageY
+            | AddressingMode::IndirectX
+            | AddressingMode::IndirectY
+            | AddressingMode::ZeroPageIndirect
+            | AddressingMode::Relative => 1,
+            AddressingMode::Absolute
+            | AddressingMode::AbsoluteX
+            | AddressingMode::AbsoluteY
+            | AddressingMode::Indirect
+            | AddressingMode::AbsoluteIndexedIndirect
+            | AddressingMode::ZeroPageRelative => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+    pub mnemonic: Mnemonic,
+    pub mode: AddressingMode,
+    // cycles when no page is crossed and no branch is taken
+    pub cycles: u8,
+    // reads through abs,X, abs,Y and (ind),Y take one more cycle when the
+    // index carries into the high byte. stores and read-modify-write
+    // instructions always spend that cycle, so it is part of their base count
+    pub page_penalty: bool,
+    // not part of the official instruction set. on the NMOS parts these only
+    // run when the CPU has undocumented opcodes turned on, the 65C02 ones are
+    // reserved NOPs
+    pub undocumented: bool,
+}
+
+impl Instruction {
+    // opcode plus operand
+    pub fn size(self) -> u16 {
+        1 + self.mode.operand_bytes()
+    }
+}
+
+// which chip the CPU behaves like. the variant picks the decode table, which
+// chips with the same instruction set share, and the CPU checks it wherever
+// the chips disagree on behavior
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Variant {
+    // the original MOS 6502
+    #[default]
+    Nmos6502,
+    // the CMOS 65C02: new instructions and the (zp) mode, JMP ($xxFF)
+    // fixed, valid N and Z in decimal mode and every undefined opcode a NOP
+    Cmos65C02,
+    // the WDC W65C02S: a 65C02 with the Rockwell bit instructions, WAI and STP
+    Wdc65C02,
+    // the NES CPU: an NMOS 6502 core with the decimal adder cut out. D is
+    // still stored and pushed, ADC, SBC and ARR just ignore it. it shares
+    // the NMOS table, so the undocumented opcodes behave the same and are
+    // still turned on with `set_undocumented_opcodes`
+    Ricoh2A03,
+}
+
+impl Variant {
+    // what the CPU does with every opcode byte, None for bytes that are not
+    // an instruction. the CPU, the disassembler and the assembler all read
+    // from here
+    pub fn decode_table(self) -> &'static [Option<Instruction>; 256] {
+        match self {
+            Variant::Nmos6502 | Variant::Ricoh2A03 => &DECODE_TABLE,
+            Variant::Cmos65C02 => &CMOS_DECODE_TABLE,
+            Variant::Wdc65C02 => &WDC_DECODE_TABLE,
+        }
+    }
+
+    // true for the parts built on the 65C02 core
+    pub fn is_cmos(self) -> bool {
+        matches!(self, Variant::Cmos65C02 | Variant::Wdc65C02)
+    }
+
+    // whether the D flag switches the adder to BCD
+    pub fn has_decimal_mode(self) -> bool {
+        self != Variant::Ricoh2A03
+    }
+
+    pub fn decode(self, opcode: Byte) -> Option<Instruction> {
+        self.decode_table()[opcode as usize]
+    }
+
+    // the opcode for an instruction. when there are aliases the documented
+    // one wins, then the lowest
+    pub fn encode(self, mnemonic: Mnemonic, mode: AddressingMode) -> Option<Byte> {
+        (0..=Byte::MAX)
+            .filter_map(|opcode| self.decode(opcode).map(|instruction| (opcode, instruction)))
+            .filter(|(_, instruction)| instruction.mnemonic == mnemonic && instruction.mode == mode)
+            .min_by_key(|(_, instruction)| instruction.undocumented)
+            .map(|(opcode, _)| opcode)
+    }
+}
+
+// the NMOS 6502 table
+pub static DECODE_TABLE: [Option<Instruction>; 256] = build_nmos_table();
+
+static CMOS_DECODE_TABLE: [Option<Instruction>; 256] = build_cmos_table();
+
+static WDC_DECODE_TABLE: [Option<Instruction>; 256] = build_wdc_table();
+
+// NMOS 6502 shorthand for `Variant::decode`
+pub fn decode(opcode: Byte) -> Option<Instruction> {
+    Variant::Nmos6502.decode(opcode)
+}
+
+// NMOS 6502 shorthand for `Variant::encode`
+pub fn encode(mnemonic: Mnemonic, mode: AddressingMode) -> Option<Byte> {
+    Variant::Nmos6502.encode(mnemonic, mode)
+}
+
+const fn op(mnemonic: Mnemonic, mode: AddressingMode, cycles: u8) -> Option<Instruction> {
+    Some(Instruction {
+        mnemonic,
+        mode,
+        cycles,
+        page_penalty: false,
+        undocumented: false,
+    })
+}
+
+const fn op_penalty(mnemonic: Mnemonic, mode: AddressingMode, cycles: u8) -> Option<Instruction> {
+    Some(Instruction {
+        mnemonic,
+        mode,
+        cycles,
+        page_penalty: true,
+        undocumented: false,
+    })
+}
+
+const fn undocumented(entry: Option<Instruction>) -> Option<Instruction> {
+    match entry {
+        Some(instruction) => Some(Instruction {
+            undocumented: true,
+            ..instruction
+        }),
+        None => None,
+    }
+}
+
+// found here : https://web.archive.org/web/20181019030759/http://obelisk.me.uk/6502/reference.html
+const fn build_nmos_table() -> [Option<Instruction>; 256] {
+    use AddressingMode::*;
+    use Mnemonic::*;
+
+    let mut table = [None; 256];
+
+    table[0xA9] = op(Lda, Immediate, 2);
+    table[0xA5] = op(Lda, ZeroPage, 3);
+    table[0xB5] = op(Lda, ZeroPageX, 4);
+    table[0xAD] = op(Lda, Absolute, 4);
+    table[0xBD] = op_penalty(Lda, AbsoluteX, 4);
+    table[0xB9] = op_penalty(Lda, AbsoluteY, 4);
+    table[0xA1] = op(Lda, IndirectX, 6);
+    table[0xB1] = op_penalty(Lda, IndirectY, 5);
+
+    table[0xA2] = op(Ldx, Immediate, 2);
+    table[0xA6] = op(Ldx, ZeroPage, 3);
+    table[0xB6] = op(Ldx, ZeroPageY, 4);
+    table[0xAE] = op(Ldx, Absolute, 4);
+    table[0xBE] = op_penalty(Ldx, AbsoluteY, 4);
+
+    table[0xA0] = op(Ldy, Immediate, 2);
+    table[0xA4] = op(Ldy, ZeroPage, 3);
+    table[0xB4] = op(Ldy, ZeroPageX, 4);
+    table[0xAC] = op(Ldy, Absolute, 4);
+    table[0xBC] = op_penalty(Ldy, AbsoluteX, 4);
+
+    table[0x85] = op(Sta, ZeroPage, 3);
+    table[0x95] = op(Sta, ZeroPageX, 4);
+    table[0x8D] = op(Sta, Absolute, 4);
+    table[0x9D] = op(Sta, AbsoluteX, 5);
+    table[0x99] = op(Sta, AbsoluteY, 5);
+    table[0x81] = op(Sta, IndirectX, 6);
+    table[0x91] = op(Sta, IndirectY, 6);
+
+    table[0x86] = op(Stx, ZeroPage, 3);
+    table[0x96] = op(Stx, ZeroPageY, 4);
+    table[0x8E] = op(Stx, Absolute, 4);
+
+    table[0x84] = op(Sty, ZeroPage, 3);
+    table[0x94] = op(Sty, ZeroPageX, 4);
+    table[0x8C] = op(Sty, Absolute, 4);
+
+    table[0xAA] = op(Tax, Implied, 2);
+    table[0xA8] = op(Tay, Implied, 2);
+    table[0x8A] = op(Txa, Implied, 2);
+    table[0x98] = op(Tya, Implied, 2);
+    table[0xBA] = op(Tsx, Implied, 2);
+    table[0x9A] = op(Txs, Implied, 2);
+
+    table[0x48] = op(Pha, Implied, 3);
+    table[0x68] = op(Pla, Implied, 4);
+    table[0x08] = op(Php, Implied, 3);
+    table[0x28] = op(Plp, Implied, 4);
+
+    table[0x29] = op(And, Immediate, 2);
+    table[0x25] = op(And, ZeroPage, 3);
+    table[0x35] = op(And, ZeroPageX, 4);
+    table[0x2D] = op(And, Absolute, 4);
+    table[0x3D] = op_penalty(And, AbsoluteX, 4);
+    table[0x39] = op_penalty(And, AbsoluteY, 4);
+    table[0x21] = op(And, IndirectX, 6);
+    table[0x31] = op_penalty(And, IndirectY, 5);
+
+    table[0x09] = op(Ora, Immediate, 2);
+    table[0x05] = op(Ora, ZeroPage, 3);
+    table[0x15] = op(Ora, ZeroPageX, 4);
+    table[0x0D] = op(Ora, Absolute, 4);
+    table[0x1D] = op_penalty(Ora, AbsoluteX, 4);
+    table[0x19] = op_penalty(Ora, AbsoluteY, 4);
+    table[0x01] = op(Ora, IndirectX, 6);
+    table[0x11] = op_penalty(Ora, IndirectY, 5);
+
+    table[0x49] = op(Eor, Immediate, 2);
+    table[0x45] = op(Eor, ZeroPage, 3);
+    table[0x55] = op(Eor, ZeroPageX, 4);
+    table[0x4D] = op(Eor, Absolute, 4);
+    table[0x5D] = op_penalty(Eor, AbsoluteX, 4);
+    table[0x59] = op_penalty(Eor, AbsoluteY, 4);
+    table[0x41] = op(Eor, IndirectX, 6);
+    table[0x51] = op_penalty(Eor, IndirectY, 5);
+
+    table[0x24] = op(Bit, ZeroPage, 3);
+    table[0x2C] = op(Bit, Absolute, 4);
+
+    table[0x69] = op(Adc, Immediate, 2);
+    table[0x65] = op(Adc, ZeroPage, 3);
+    table[0x75] = op(Adc, ZeroPageX, 4);
+    table[0x6D] = op(Adc, Absolute, 4);
+    table[0x7D] = op_penalty(Adc, AbsoluteX, 4);
+    table[0x79] = op_penalty(Adc, AbsoluteY, 4);
+    table[0x61] = op(Adc, IndirectX, 6);
+    table[0x71] = op_penalty(Adc, IndirectY, 5);
+
+    table[0xE9] = op(Sbc, Immediate, 2);
+    table[0xE5] = op(Sbc, ZeroPage, 3);
+    table[0xF5] = op(Sbc, ZeroPageX, 4);
+    table[0xED] = op(Sbc, Absolute, 4);
+    table[0xFD] = op_penalty(Sbc, AbsoluteX, 4);
+    table[0xF9] = op_penalty(Sbc, AbsoluteY, 4);
+    table[0xE1] = op(Sbc, IndirectX, 6);
+    table[0xF1] = op_penalty(Sbc, IndirectY, 5);
+    // undocumented, but identical to $E9
+    table[0xEB] = undocumented(op(Sbc, Immediate, 2));
+
+    table[0xC9] = op(Cmp, Immediate, 2);
+    table[0xC5] = op(Cmp, ZeroPage, 3);
+    table[0xD5] = op(Cmp, ZeroPageX, 4);
+    table[0xCD] = op(Cmp, Absolute, 4);
+    table[0xDD] = op_penalty(Cmp, AbsoluteX, 4);
+    table[0xD9] = op_penalty(Cmp, AbsoluteY, 4);
+    table[0xC1] = op(Cmp, IndirectX, 6);
+    table[0xD1] = op_penalty(Cmp, IndirectY, 5);
+
+    table[0xE0] = op(Cpx, Immediate, 2);
+    table[0xE4] = op(Cpx, ZeroPage, 3);
+    table[0xEC] = op(Cpx, Absolute, 4);
+
+    table[0xC0] = op(Cpy, Immediate, 2);
+    table[0xC4] = op(Cpy, ZeroPage, 3);
+    table[0xCC] = op(Cpy, Absolute, 4);
+
+    table[0xE6] = op(Inc, ZeroPage, 5);
+    table[0xF6] = op(Inc, ZeroPageX, 6);
+    table[0xEE] = op(Inc, Absolute, 6);
+    table[0xFE] = op(Inc, AbsoluteX, 7);
+    table[0xE8] = op(Inx, Implied, 2);
+    table[0xC8] = op(Iny, Implied, 2);
+
+    table[0xC6] = op(Dec, ZeroPage, 5);
+    table[0xD6] = op(Dec, ZeroPageX, 6);
+    table[0xCE] = op(Dec, Absolute, 6);
+    table[0xDE] = op(Dec, AbsoluteX, 7);
+    table[0xCA] = op(Dex, Implied, 2);
+    table[0x88] = op(Dey, Implied, 2);
+
+    table[0x0A] = op(Asl, Accumulator, 2);
+    table[0x06] = op(Asl, ZeroPage, 5);
+    table[0x16] = op(Asl, ZeroPageX, 6);
+    table[0x0E] = op(Asl, Absolute, 6);
+    table[0x1E] = op(Asl, AbsoluteX, 7);
+
+    table[0x4A] = op(Lsr, Accumulator, 2);
+    table[0x46] = op(Lsr, ZeroPage, 5);
+    table[0x56] = op(Lsr, ZeroPageX, 6);
+    table[0x4E] = op(Lsr, Absolute, 6);
+    table[0x5E] = op(Lsr, AbsoluteX, 7);
+
+    table[0x2A] = op(Rol, Accumulator, 2);
+    table[0x26] = op(Rol, ZeroPage, 5);
+    table[0x36] = op(Rol, ZeroPageX, 6);
+    table[0x2E] = op(Rol, Absolute, 6);
+    table[0x3E] = op(Rol, AbsoluteX, 7);
+
+    table[0x6A] = op(Ror, Accumulator, 2);
+    table[0x66] = op(Ror, ZeroPage, 5);
+    table[0x76] = op(Ror, ZeroPageX, 6);
+    table[0x6E] = op(Ror, Absolute, 6);
+    table[0x7E] = op(Ror, AbsoluteX, 7);
+
+    table[0x4C] = op(Jmp, Absolute, 3);
+    table[0x6C] = op(Jmp, Indirect, 5);
+    table[0x20] = op(Jsr, Absolute, 6);
+    table[0x60] = op(Rts, Implied, 6);
+
+    table[0x90] = op(Bcc, Relative, 2);
+    table[0xB0] = op(Bcs, Relative, 2);
+    table[0xF0] = op(Beq, Relative, 2);
+    table[0x30] = op(Bmi, Relative, 2);
+    table[0xD0] = op(Bne, Relative, 2);
+    table[0x10] = op(Bpl, Relative, 2);
+    table[0x50] = op(Bvc, Relative, 2);
+    table[0x70] = op(Bvs, Relative, 2);
+
+    table[0x18] = op(Clc, Implied, 2);
+    table[0xD8] = op(Cld, Implied, 2);
+    table[0x58] = op(Cli, Implied, 2);
+    table[0xB8] = op(Clv, Implied, 2);
+    table[0x38] = op(Sec, Implied, 2);
+    table[0xF8] = op(Sed, Implied, 2);
+    table[0x78] = op(Sei, Implied, 2);
+
+    table[0x00] = op(Brk, Implied, 7);
+    table[0xEA] = op(Nop, Implied, 2);
+    table[0x40] = op(Rti, Implied, 6);
+
+    // stable undocumented opcodes, as described in "NMOS 6510 Unintended
+    // Opcodes" (No More Secrets). the read-modify-write ones pair a
+    // shift or INC/DEC with an ALU op and get the extra abs,Y and (ind),Y
+    // forms, which always pay the indexing cycle like any other write
+    table[0x07] = undocumented(op(Slo, ZeroPage, 5));
+    table[0x17] = undocumented(op(Slo, ZeroPageX, 6));
+    table[0x0F] = undocumented(op(Slo, Absolute, 6));
+    table[0x1F] = undocumented(op(Slo, AbsoluteX, 7));
+    table[0x1B] = undocumented(op(Slo, AbsoluteY, 7));
+    table[0x03] = undocumented(op(Slo, IndirectX, 8));
+    table[0x13] = undocumented(op(Slo, IndirectY, 8));
+
+    table[0x27] = undocumented(op(Rla, ZeroPage, 5));
+    table[0x37] = undocumented(op(Rla, ZeroPageX, 6));
+    table[0x2F] = undocumented(op(Rla, Absolute, 6));
+    table[0x3F] = undocumented(op(Rla, AbsoluteX, 7));
+    table[0x3B] = undocumented(op(Rla, AbsoluteY, 7));
+    table[0x23] = undocumented(op(Rla, IndirectX, 8));
+    table[0x33] = undocumented(op(Rla, IndirectY, 8));
+
+    table[0x47] = undocumented(op(Sre, ZeroPage, 5));
+    table[0x57] = undocumented(op(Sre, ZeroPageX, 6));
+    table[0x4F] = undocumented(op(Sre, Absolute, 6));
+    table[0x5F] = undocumented(op(Sre, AbsoluteX, 7));
+    table[0x5B] = undocumented(op(Sre, AbsoluteY, 7));
+    table[0x43] = undocumented(op(Sre, IndirectX, 8));
+    table[0x53] = undocumented(op(Sre, IndirectY, 8));
+
+    table[0x67] = undocumented(op(Rra, ZeroPage, 5));
+    table[0x77] = undocumented(op(Rra, ZeroPageX, 6));
+    table[0x6F] = undocumented(op(Rra, Absolute, 6));
+    table[0x7F] = undocumented(op(Rra, AbsoluteX, 7));
+    table[0x7B] = undocumented(op(Rra, AbsoluteY, 7));
+    table[0x63] = undocumented(op(Rra, IndirectX, 8));
+    table[0x73] = undocumented(op(Rra, IndirectY, 8));
+
+    table[0xC7] = undocumented(op(Dcp, ZeroPage, 5));
+    table[0xD7] = undocumented(op(Dcp, ZeroPageX, 6));
+    table[0xCF] = undocumented(op(Dcp, Absolute, 6));
+    table[0xDF] = undocumented(op(Dcp, AbsoluteX, 7));
+    table[0xDB] = undocumented(op(Dcp, AbsoluteY, 7));
+    table[0xC3] = undocumented(op(Dcp, IndirectX, 8));
+    table[0xD3] = undocumented(op(Dcp, IndirectY, 8));
+
+    table[0xE7] = undocumented(op(Isc, ZeroPage, 5));
+    table[0xF7] = undocumented(op(Isc, ZeroPageX, 6));
+    table[0xEF] = undocumented(op(Isc, Absolute, 6));
+    table[0xFF] = undocumented(op(Isc, AbsoluteX, 7));
+    table[0xFB] = undocumented(op(Isc, AbsoluteY, 7));
+    table[0xE3] = undocumented(op(Isc, IndirectX, 8));
+    table[0xF3] = undocumented(op(Isc, IndirectY, 8));
+
+    table[0x87] = undocumented(op(Sax, ZeroPage, 3));
+    table[0x97] = undocumented(op(Sax, ZeroPageY, 4));
+    table[0x8F] = undocumented(op(Sax, Absolute, 4));
+    table[0x83] = undocumented(op(Sax, IndirectX, 6));
+
+    table[0xA7] = undocumented(op(Lax, ZeroPage, 3));
+    table[0xB7] = undocumented(op(Lax, ZeroPageY, 4));
+    table[0xAF] = undocumented(op(Lax, Absolute, 4));
+    table[0xBF] = undocumented(op_penalty(Lax, AbsoluteY, 4));
+    table[0xA3] = undocumented(op(Lax, IndirectX, 6));
+    table[0xB3] = undocumented(op_penalty(Lax, IndirectY, 5));
+
+    table[0x0B] = undocumented(op(Anc, Immediate, 2));
+    table[0x2B] = undocumented(op(Anc, Immediate, 2));
+    table[0x4B] = undocumented(op(Alr, Immediate, 2));
+    table[0x6B] = undocumented(op(Arr, Immediate, 2));
+    table[0xCB] = undocumented(op(Sbx, Immediate, 2));
+
+    // NOPs that still fetch their operand, and read it from memory
+    table[0x1A] = undocumented(op(Nop, Implied, 2));
+    table[0x3A] = undocumented(op(Nop, Implied, 2));
+    table[0x5A] = undocumented(op(Nop, Implied, 2));
+    table[0x7A] = undocumented(op(Nop, Implied, 2));
+    table[0xDA] = undocumented(op(Nop, Implied, 2));
+    table[0xFA] = undocumented(op(Nop, Implied, 2));
+    table[0x80] = undocumented(op(Nop, Immediate, 2));
+    table[0x82] = undocumented(op(Nop, Immediate, 2));
+    table[0x89] = undocumented(op(Nop, Immediate, 2));
+    table[0xC2] = undocumented(op(Nop, Immediate, 2));
+    table[0xE2] = undocumented(op(Nop, Immediate, 2));
+    table[0x04] = undocumented(op(Nop, ZeroPage, 3));
+    table[0x44] = undocumented(op(Nop, ZeroPage, 3));
+    table[0x64] = undocumented(op(Nop, ZeroPage, 3));
+    table[0x14] = undocumented(op(Nop, ZeroPageX, 4));
+    table[0x34] = undocumented(op(Nop, ZeroPageX, 4));
+    table[0x54] = undocumented(op(Nop, ZeroPageX, 4));
+    table[0x74] = undocumented(op(Nop, ZeroPageX, 4));
+    table[0xD4] = undocumented(op(Nop, ZeroPageX, 4));
+    table[0xF4] = undocumented(op(Nop, ZeroPageX, 4));
+    table[0x0C] = undocumented(op(Nop, Absolute, 4));
+    table[0x1C] = undocumented(op_penalty(Nop, AbsoluteX, 4));
+    table[0x3C] = undocumented(op_penalty(Nop, AbsoluteX, 4));
+    table[0x5C] = undocumented(op_penalty(Nop, AbsoluteX, 4));
+    table[0x7C] = undocumented(op_penalty(Nop, AbsoluteX, 4));
+    table[0xDC] = undocumented(op_penalty(Nop, AbsoluteX, 4));
+    table[0xFC] = undocumented(op_penalty(Nop, AbsoluteX, 4));
+
+    // unstable undocumented opcodes. ANE and LXA depend on a chip-specific
+    // magic constant, the SH* stores AND their value with the high byte of
+    // the base address plus one
+    table[0x8B] = undocumented(op(Ane, Immediate, 2));
+    table[0xAB] = undocumented(op(Lxa, Immediate, 2));
+    table[0xBB] = undocumented(op_penalty(Las, AbsoluteY, 4));
+    table[0x9F] = undocumented(op(Sha, AbsoluteY, 5));
+    table[0x93] = undocumented(op(Sha, IndirectY, 6));
+    table[0x9E] = undocumented(op(Shx, AbsoluteY, 5));
+    table[0x9C] = undocumented(op(Shy, AbsoluteX, 5));
+    table[0x9B] = undocumented(op(Tas, AbsoluteY, 5));
+
+    table[0x02] = undocumented(op(Jam, Implied, 2));
+    table[0x12] = undocumented(op(Jam, Implied, 2));
+    table[0x22] = undocumented(op(Jam, Implied, 2));
+    table[0x32] = undocumented(op(Jam, Implied, 2));
+    table[0x42] = undocumented(op(Jam, Implied, 2));
+    table[0x52] = undocumented(op(Jam, Implied, 2));
+    table[0x62] = undocumented(op(Jam, Implied, 2));
+    table[0x72] = undocumented(op(Jam, Implied, 2));
+    table[0x92] = undocumented(op(Jam, Implied, 2));
+    table[0xB2] = undocumented(op(Jam, Implied, 2));
+    table[0xD2] = undocumented(op(Jam, Implied, 2));
+    table[0xF2] = undocumented(op(Jam, Implied, 2));
+
+    table
+}
+
+// the documented NMOS set plus the 65C02 additions, from the WDC W65C02S
+// datasheet. the reserved opcodes are NOPs of fixed size and timing
+const fn build_cmos_table() -> [Option<Instruction>; 256] {
+    use AddressingMode::*;
+    use Mnemonic::*;
+
+    let nmos = build_nmos_table();
+    let mut table = [None; 256];
+    let mut opcode = 0;
+    while opcode < 256 {
+        if let Some(instruction) = nmos[opcode]
+            && !instruction.undocumented
+        {
+            table[opcode] = Some(instruction);
+        }
+        opcode += 1;
+    }
+
+    // JMP ($xxFF) reads the high byte from the next page, which costs a cycle
+    table[0x6C] = op(Jmp, Indirect, 6);
+    // shifts and rotates on abs,X only pay the indexing cycle on a page cross
+    table[0x1E] = op_penalty(Asl, AbsoluteX, 6);
+    table[0x5E] = op_penalty(Lsr, AbsoluteX, 6);
+    table[0x3E] = op_penalty(Rol, AbsoluteX, 6);
+    table[0x7E] = op_penalty(Ror, AbsoluteX, 6);
+
+    table[0x12] = op(Ora, ZeroPageIndirect, 5);
+    table[0x32] = op(And, ZeroPageIndirect, 5);
+    table[0x52] = op(Eor, ZeroPageIndirect, 5);
+    table[0x72] = op(Adc, ZeroPageIndirect, 5);
+    table[0x92] = op(Sta, ZeroPageIndirect, 5);
+    table[0xB2] = op(Lda, ZeroPageIndirect, 5);
+    table[0xD2] = op(Cmp, ZeroPageIndirect, 5);
+    table[0xF2] = op(Sbc, ZeroPageIndirect, 5);
+
+    table[0x89] = op(Bit, Immediate, 2);
+    table[0x34] = op(Bit, ZeroPageX, 4);
+    table[0x3C] = op_penalty(Bit, AbsoluteX, 4);
+
+    table[0x1A] = op(Inc, Accumulator, 2);
+    table[0x3A] = op(Dec, Accumulator, 2);
+
+    table[0x80] = op(Bra, Relative, 2);
+    table[0x7C] = op(Jmp, AbsoluteIndexedIndirect, 6);
+
+    table[0xDA] = op(Phx, Implied, 3);
+    table[0x5A] = op(Phy, Implied, 3);
+    table[0xFA] = op(Plx, Implied, 4);
+    table[0x7A] = op(Ply, Implied, 4);
+
+    table[0x64] = op(Stz, ZeroPage, 3);
+    table[0x74] = op(Stz, ZeroPageX, 4);
+    table[0x9C] = op(Stz, Absolute, 4);
+    table[0x9E] = op(Stz, AbsoluteX, 5);
+
+    table[0x04] = op(Tsb, ZeroPage, 5);
+    table[0x0C] = op(Tsb, Absolute, 6);
+    table[0x14] = op(Trb, ZeroPage, 5);
+    table[0x1C] = op(Trb, Absolute, 6);
+
+    // $EB is no longer an SBC alias
+    table[0xEB] = None;
+
+    table[0x44] = undocumented(op(Nop, ZeroPage, 3));
+    table[0x54] = undocumented(op(Nop, ZeroPageX, 4));
+    table[0xD4] = undocumented(op(Nop, ZeroPageX, 4));
+    table[0xF4] = undocumented(op(Nop, ZeroPageX, 4));
+    table[0x5C] = undocumented(op(Nop, Absolute, 8));
+    table[0xDC] = undocumented(op(Nop, Absolute, 4));
+    table[0xFC] = undocumented(op(Nop, Absolute, 4));
+
+    // what is left is column 2, which skips an operand byte, and columns 3,
+    // 7, B and F, which are single-cycle, single-byte NOPs
+    let mut opcode = 0;
+    while opcode < 256 {
+        if table[opcode].is_none() {
+            table[opcode] = match opcode & 0x0F {
+                0x02 => undocumented(op(Nop, Immediate, 2)),
+                _ => undocumented(op(Nop, Implied, 1)),
+            };
+        }
+        opcode += 1;
+    }
+
+    table
+}
+
+// the 65C02 with the bit instructions in columns 7 and F, WAI at $CB and STP
+// at $DB, all taken from reserved NOPs
+const fn build_wdc_table() -> [Option<Instruction>; 256] {
+    use AddressingMode::*;
+    use Mnemonic::*;
+
+    let mut table = build_cmos_table();
+
+    let rmb = [Rmb0, Rmb1, Rmb2, Rmb3, Rmb4, Rmb5, Rmb6, Rmb7];
+    let smb = [Smb0, Smb1, Smb2, Smb3, Smb4, Smb5, Smb6, Smb7];
+    let bbr = [Bbr0, Bbr1, Bbr2, Bbr3, Bbr4, Bbr5, Bbr6, Bbr7];
+    let bbs = [Bbs0, Bbs1, Bbs2, Bbs3, Bbs4, Bbs5, Bbs6, Bbs7];
+    let mut bit = 0;
+    while bit < 8 {
+        table[bit << 4 | 0x07] = op(rmb[bit], ZeroPage, 5);
+        table[bit << 4 | 0x87] = op(smb[bit], ZeroPage, 5);
+        table[bit << 4 | 0x0F] = op(bbr[bit], ZeroPageRelative, 5);
+        table[bit << 4 | 0x8F] = op(bbs[bit], ZeroPageRelative, 5);
+        bit += 1;
+    }
+
+    table[0xCB] = op(Wai, Implied, 3);
+    table[0xDB] = op(Stp, Implied, 3);
+
+    table
+}
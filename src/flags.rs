@@ -0,0 +1,25 @@
+// modular-bitfield 0.11 wraps every field type in parentheses when it expands
+// `#[bitfield]`, which newer compilers flag as unused_parens
+#![allow(unused_parens)]
+
+use modular_bitfield::prelude::*;
+
+// the generated accessors are only for the CPU, callers go through `CPU`
+#[bitfield]
+#[derive(Debug, Clone, Copy)]
+pub struct CpuFlags {
+    pub(crate) carry: bool,
+    pub(crate) zero: bool,
+    pub(crate) interrupt_disable: bool,
+    pub(crate) decimal: bool,
+    pub(crate) break_command: bool,
+    pub(crate) unused: bool,
+    pub(crate) overflow: bool,
+    pub(crate) negative: bool,
+}
+
+impl Default for CpuFlags {
+    fn default() -> Self {
+        Self::new()
+    }
+}
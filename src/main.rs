@@ -1,12 +1,38 @@
-use modular_bitfield::prelude::*;
-use num_enum::TryFromPrimitive;
-use std::convert::TryFrom;
+use std::fmt;
 use std::ops::{Index, IndexMut};
 
+mod decode;
+mod flags;
+mod memory_map;
+
+pub use decode::{AddressingMode, DECODE_TABLE, Instruction, Mnemonic, Variant, decode, encode};
+pub use flags::CpuFlags;
+pub use memory_map::{MemoryMap, MemoryMapBuilder, MemoryMapError};
+
 type Word = u16;
 type Byte = u8;
 const MAX_MEM: usize = 65536;
 
+const STACK_BASE: Word = 0x0100;
+const NMI_VECTOR: Word = 0xFFFA;
+const RESET_VECTOR: Word = 0xFFFC;
+const IRQ_VECTOR: Word = 0xFFFE;
+// reset, IRQ and NMI all take as long as BRK
+const INTERRUPT_CYCLES: u32 = 7;
+
+// everything the CPU reads or writes goes through a bus, so ROM, I/O chips
+// and mirrored regions can sit behind the same addresses a real machine has
+pub trait Bus {
+    fn read(&mut self, addr: Word) -> Byte;
+    fn write(&mut self, addr: Word, value: Byte);
+    // read without side effects, for debuggers and tests
+    fn peek(&self, addr: Word) -> Byte;
+    // called after every step with the CPU's total cycle count, so timers,
+    // video and other devices can catch up to the same point in time
+    fn sync(&mut self, _total_cycles: u64) {}
+}
+
+// flat 64 KiB of RAM, the default bus
 pub struct Mem {
     data: [Byte; MAX_MEM],
 }
@@ -16,14 +42,23 @@ impl Mem {
         Mem { data: [0; MAX_MEM] }
     }
 
+    // a word at $FFFF has its high byte at $0000
     pub fn write_word(&mut self, addr: usize, value: Word) {
         self.data[addr] = (value & 0xFF) as Byte;
-        self.data[addr + 1] = ((value >> 8) & 0xFF) as Byte;
+        self.data[(addr + 1) % MAX_MEM] = ((value >> 8) & 0xFF) as Byte;
     }
 
     pub fn read_word(&self, addr: usize) -> Word {
         let low_byte = self.data[addr] as Word;
-        let high_byte = self.data[addr + 1] as Word;
+        let high_byte = self.data[(addr + 1) % MAX_MEM] as Word;
+        low_byte | (high_byte << 8)
+    }
+
+    // a pointer at $FF takes its high byte from $00, the way (ind,X) and
+    // (ind),Y read it
+    pub fn read_word_zero_page(&self, addr: Byte) -> Word {
+        let low_byte = self.data[addr as usize] as Word;
+        let high_byte = self.data[addr.wrapping_add(1) as usize] as Word;
         low_byte | (high_byte << 8)
     }
 }
@@ -34,6 +69,20 @@ impl Default for Mem {
     }
 }
 
+impl Bus for Mem {
+    fn read(&mut self, addr: Word) -> Byte {
+        self.data[addr as usize]
+    }
+
+    fn write(&mut self, addr: Word, value: Byte) {
+        self.data[addr as usize] = value;
+    }
+
+    fn peek(&self, addr: Word) -> Byte {
+        self.data[addr as usize]
+    }
+}
+
 impl Index<usize> for Mem {
     type Output = Byte;
 
@@ -48,252 +97,1351 @@ impl IndexMut<usize> for Mem {
     }
 }
 
-// found here : https://web.archive.org/web/20181019030759/http://obelisk.me.uk/6502/reference.html
+// names for the documented opcode bytes, plus the undocumented $EB alias of
+// SBC #, for building programs by hand. what each one does lives in the
+// decode table
 #[repr(u8)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Opcode {
     LdaIm = 0xA9,
-    LdaZp = 0x45,
+    LdaZp = 0xA5,
     LdaZpx = 0xB5,
     LdaAbs = 0xAD,
     LdaAbsX = 0xBD,
     LdaAbsY = 0xB9,
     LdaInX = 0xA1,
     LdaInY = 0xB1,
+    LdxIm = 0xA2,
+    LdxZp = 0xA6,
+    LdxZpy = 0xB6,
+    LdxAbs = 0xAE,
+    LdxAbsY = 0xBE,
+    LdyIm = 0xA0,
+    LdyZp = 0xA4,
+    LdyZpx = 0xB4,
+    LdyAbs = 0xAC,
+    LdyAbsX = 0xBC,
+    StaZp = 0x85,
+    StaZpx = 0x95,
+    StaAbs = 0x8D,
+    StaAbsX = 0x9D,
+    StaAbsY = 0x99,
+    StaInX = 0x81,
+    StaInY = 0x91,
+    StxZp = 0x86,
+    StxZpy = 0x96,
+    StxAbs = 0x8E,
+    StyZp = 0x84,
+    StyZpx = 0x94,
+    StyAbs = 0x8C,
+    Tax = 0xAA,
+    Tay = 0xA8,
+    Txa = 0x8A,
+    Tya = 0x98,
+    Tsx = 0xBA,
+    Txs = 0x9A,
+    Pha = 0x48,
+    Pla = 0x68,
+    Php = 0x08,
+    Plp = 0x28,
+    AndIm = 0x29,
+    AndZp = 0x25,
+    AndZpx = 0x35,
+    AndAbs = 0x2D,
+    AndAbsX = 0x3D,
+    AndAbsY = 0x39,
+    AndInX = 0x21,
+    AndInY = 0x31,
+    OraIm = 0x09,
+    OraZp = 0x05,
+    OraZpx = 0x15,
+    OraAbs = 0x0D,
+    OraAbsX = 0x1D,
+    OraAbsY = 0x19,
+    OraInX = 0x01,
+    OraInY = 0x11,
+    EorIm = 0x49,
+    EorZp = 0x45,
+    EorZpx = 0x55,
+    EorAbs = 0x4D,
+    EorAbsX = 0x5D,
+    EorAbsY = 0x59,
+    EorInX = 0x41,
+    EorInY = 0x51,
+    BitZp = 0x24,
+    BitAbs = 0x2C,
+    CmpIm = 0xC9,
+    CmpZp = 0xC5,
+    CmpZpx = 0xD5,
+    CmpAbs = 0xCD,
+    CmpAbsX = 0xDD,
+    CmpAbsY = 0xD9,
+    CmpInX = 0xC1,
+    CmpInY = 0xD1,
+    CpxIm = 0xE0,
+    CpxZp = 0xE4,
+    CpxAbs = 0xEC,
+    CpyIm = 0xC0,
+    CpyZp = 0xC4,
+    CpyAbs = 0xCC,
+    Inx = 0xE8,
+    Iny = 0xC8,
+    Dex = 0xCA,
+    Dey = 0x88,
+    IncZp = 0xE6,
+    IncZpx = 0xF6,
+    IncAbs = 0xEE,
+    IncAbsX = 0xFE,
+    DecZp = 0xC6,
+    DecZpx = 0xD6,
+    DecAbs = 0xCE,
+    DecAbsX = 0xDE,
+    AslAcc = 0x0A,
+    AslZp = 0x06,
+    AslZpx = 0x16,
+    AslAbs = 0x0E,
+    AslAbsX = 0x1E,
+    LsrAcc = 0x4A,
+    LsrZp = 0x46,
+    LsrZpx = 0x56,
+    LsrAbs = 0x4E,
+    LsrAbsX = 0x5E,
+    RolAcc = 0x2A,
+    RolZp = 0x26,
+    RolZpx = 0x36,
+    RolAbs = 0x2E,
+    RolAbsX = 0x3E,
+    RorAcc = 0x6A,
+    RorZp = 0x66,
+    RorZpx = 0x76,
+    RorAbs = 0x6E,
+    RorAbsX = 0x7E,
+    Clc = 0x18,
+    Sec = 0x38,
+    Cli = 0x58,
+    Sei = 0x78,
+    Cld = 0xD8,
+    Sed = 0xF8,
+    Clv = 0xB8,
+    Nop = 0xEA,
+    Brk = 0x00,
     Jsr = 0x20,
+    Rts = 0x60,
+    Rti = 0x40,
+    JmpAbs = 0x4C,
+    JmpInd = 0x6C,
+    Bpl = 0x10,
+    Bmi = 0x30,
+    Bvc = 0x50,
+    Bvs = 0x70,
+    Bcc = 0x90,
+    Bcs = 0xB0,
+    Bne = 0xD0,
+    Beq = 0xF0,
     AdcIm = 0x69,
     AdcZp = 0x65,
     AdcZpx = 0x75,
     AdcAbs = 0x6D,
     AdcAbsX = 0x7D,
+    AdcAbsY = 0x79,
+    AdcInX = 0x61,
+    AdcInY = 0x71,
+    SbcIm = 0xE9,
+    SbcZp = 0xE5,
+    SbcZpx = 0xF5,
+    SbcAbs = 0xED,
+    SbcAbsX = 0xFD,
+    SbcAbsY = 0xF9,
+    SbcInX = 0xE1,
+    SbcInY = 0xF1,
+    // undocumented, behaves exactly like SbcIm but only runs with
+    // `set_undocumented_opcodes(true)`
+    SbcImAlias = 0xEB,
 }
 
-#[bitfield]
-#[derive(Debug, Clone, Copy)]
-pub struct CpuFlags {
-    carry: bool,
-    zero: bool,
-    interrupt_disable: bool,
-    decimal: bool,
-    break_command: bool,
-    unused: bool,
-    overflow: bool,
-    negative: bool,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuError {
+    // `program_counter` is the address the opcode was fetched from
+    InvalidOpcode { opcode: Byte, program_counter: Word },
 }
 
-impl Default for CpuFlags {
-    fn default() -> Self {
-        Self::new()
+impl fmt::Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CpuError::InvalidOpcode {
+                opcode,
+                program_counter,
+            } => write!(f, "invalid opcode ${opcode:02X} at ${program_counter:04X}"),
+        }
     }
 }
 
+impl std::error::Error for CpuError {}
+
+// what `execute` does when it fetches a byte that is not an opcode
+pub enum InvalidOpcodePolicy {
+    // stop and return the error
+    Halt,
+    // skip the byte as a two-cycle NOP
+    Nop,
+    // hand the error to a callback, Ok carries on like Nop and Err halts
+    Trap(Box<dyn FnMut(CpuError) -> Result<(), CpuError> + Send>),
+}
+
+// everything needed to put a CPU back exactly where it was, including the
+// interrupt lines and the cycle count. configuration such as the invalid
+// opcode policy is not part of the snapshot
+#[derive(Debug, Clone)]
+pub struct CpuSnapshot {
+    program_counter: Word,
+    stack_register: Byte,
+    accumulator: Byte,
+    index_register_x: Byte,
+    index_register_y: Byte,
+    flags: CpuFlags,
+    irq_line: bool,
+    nmi_line: bool,
+    nmi_pending: bool,
+    pending_interrupt: Option<Interrupt>,
+    halt_state: Option<HaltState>,
+    total_cycles: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interrupt {
+    Reset,
+    Irq,
+    Nmi,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepResult {
+    pub cycles: u32,
+    // None when the step ran a reset or interrupt sequence instead of an
+    // instruction
+    pub opcode: Option<Byte>,
+    // the sequence that ran, including an NMI that hijacked BRK or IRQ
+    pub interrupt: Option<Interrupt>,
+    // set when the CPU is halted after this step
+    pub halted: Option<HaltState>,
+}
+
+// why the CPU has stopped running instructions. the clock keeps going, but
+// every step is an idle cycle until the CPU wakes up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HaltState {
+    // a JAM/KIL opcode locked up the CPU, `program_counter` is where it sits.
+    // only `reset` gets it going again
+    Jammed { program_counter: Word },
+    // WAI, asleep until IRQ or NMI is asserted. with I set an IRQ only wakes
+    // the CPU, which carries on after the WAI without taking the interrupt
+    Waiting,
+    // STP, stopped until `reset`
+    Stopped,
+}
+
 pub struct CPU {
     program_counter: Word,
-    stack_register: Word,
+    // SP is only the low byte, the stack always lives in page $01
+    stack_register: Byte,
     accumulator: Byte,
     index_register_x: Byte,
     index_register_y: Byte,
     flags: CpuFlags,
+    // IRQ is level triggered, so only the line is stored. NMI is edge
+    // triggered: `nmi_pending` latches the edge until it is serviced
+    irq_line: bool,
+    nmi_line: bool,
+    nmi_pending: bool,
+    // interrupts are polled at the end of each instruction and taken before
+    // the next one is fetched
+    pending_interrupt: Option<Interrupt>,
+    // IRQ and NMI only wake a CPU that is waiting, the other halt states
+    // need a reset
+    halt_state: Option<HaltState>,
+    // jump straight to $FFFC on reset instead of going through the vector
+    legacy_reset: bool,
+    invalid_opcode_policy: InvalidOpcodePolicy,
+    // run the undocumented NMOS opcodes, JAM included, instead of treating
+    // them as invalid
+    undocumented_opcodes: bool,
+    // the chip-specific constant ANE and LXA OR into A, see `set_magic_constant`
+    magic_constant: Byte,
+    // every cycle run since the CPU was created, never reset
+    total_cycles: u64,
+    variant: Variant,
 }
 
 impl Default for CPU {
     fn default() -> Self {
         CPU {
             program_counter: 0xFFFC,
-            stack_register: 0x0100,
+            stack_register: 0x00,
             accumulator: 0,
             index_register_x: 0,
             index_register_y: 0,
             flags: CpuFlags::new(),
+            irq_line: false,
+            nmi_line: false,
+            nmi_pending: false,
+            pending_interrupt: None,
+            halt_state: None,
+            legacy_reset: false,
+            invalid_opcode_policy: InvalidOpcodePolicy::Halt,
+            undocumented_opcodes: false,
+            magic_constant: 0xEE,
+            total_cycles: 0,
+            variant: Variant::Nmos6502,
         }
     }
 }
 
 impl CPU {
+    // `CPU::default()` is an NMOS 6502
+    pub fn new(variant: Variant) -> Self {
+        CPU {
+            variant,
+            ..CPU::default()
+        }
+    }
+
+    // the reset sequence itself runs as the first seven cycles of the next
+    // `execute`, like an interrupt that pushes nothing
     pub fn reset(&mut self) {
-        self.program_counter = 0xFFFC;
-        self.stack_register = 0x00FF;
-        self.accumulator = 0;
-        self.index_register_x = 0;
-        self.index_register_y = 0;
-        self.flags = CpuFlags::new();
-    }
-
-    pub fn execute(&mut self, memory: &mut Mem, mut cycles: u32) {
-        while cycles > 0 {
-            let instruction = self.fetch_byte(&mut cycles, memory);
-
-            match Opcode::try_from(instruction) {
-                Ok(Opcode::LdaIm) => {
-                    self.accumulator = self.fetch_byte(&mut cycles, memory);
-                    self.lda_set_status();
-                }
-                Ok(Opcode::LdaZp) => {
-                    let addr = self.fetch_byte(&mut cycles, memory);
-                    self.accumulator = self.read_byte(addr, &mut cycles, memory);
-                    self.lda_set_status();
-                }
-                Ok(Opcode::LdaZpx) => {
-                    let mut addr = self.fetch_byte(&mut cycles, memory);
-                    addr += self.index_register_x;
-                    cycles -= 1;
-                    self.accumulator = self.read_byte(addr, &mut cycles, memory);
-                    self.lda_set_status();
-                }
-                Ok(Opcode::LdaAbs) => {
-                    let addr = self.fetch_word(&mut cycles, memory);
-                    self.accumulator = memory[addr as usize];
-                    cycles -= 2;
-                    self.lda_set_status();
-                }
-                Ok(Opcode::LdaAbsX) => {
-                    let base_addr = self.fetch_word(&mut cycles, memory);
-                    let addr = base_addr + self.index_register_x as u16;
-                    self.accumulator = memory[addr as usize];
-
-                    let page_crossed = (base_addr & 0xFF00)
-                        != ((base_addr + self.index_register_x as u16) & 0xFF00);
-                    cycles -= 2;
-                    if page_crossed {
-                        cycles -= 1;
-                    }
-                    self.lda_set_status();
-                }
-                Ok(Opcode::LdaAbsY) => {
-                    let base_addr = self.fetch_word(&mut cycles, memory);
-                    let addr = base_addr + self.index_register_y as u16;
-                    self.accumulator = memory[addr as usize];
-
-                    let page_crossed = (base_addr & 0xFF00)
-                        != ((base_addr + self.index_register_y as u16) & 0xFF00);
-                    cycles -= 2;
-                    if page_crossed {
-                        cycles -= 1;
-                    }
-                    self.lda_set_status();
-                }
-                Ok(Opcode::LdaInX) => {
-                    let zero_page_addr = self.fetch_byte(&mut cycles, memory);
-                    let indirect_addr = (zero_page_addr + self.index_register_x) & 0xFF;
-                    cycles -= 1;
-
-                    let effective_addr_low = memory[indirect_addr as usize] as Word;
-                    let effective_addr_high = memory[(indirect_addr as usize) & 0xFF] as Word;
-                    let effective_addr = effective_addr_low | (effective_addr_high << 8);
-                    cycles -= 2;
-
-                    self.accumulator = memory[effective_addr as usize];
-                    cycles -= 1;
-                    self.lda_set_status();
-                }
-                Ok(Opcode::LdaInY) => {
-                    let zero_page_addr = self.fetch_byte(&mut cycles, memory);
-
-                    let indirect_addr_low = memory[zero_page_addr as usize] as Word;
-                    let indirect_addr_high = memory[(zero_page_addr as usize) & 0xFF] as Word;
-                    let base_addr = indirect_addr_low | (indirect_addr_high << 8);
-                    cycles -= 2;
-
-                    let page_crossed = (base_addr & 0xFF00)
-                        != ((base_addr + self.index_register_y as u16) & 0xFF00);
-                    let effective_addr = base_addr + self.index_register_y as u16;
-
-                    self.accumulator = memory[effective_addr as usize];
-                    cycles -= 1;
-                    if page_crossed {
-                        cycles -= 1;
-                    }
-                    self.lda_set_status();
-                }
-                Ok(Opcode::AdcIm) => {
-                    let value = self.fetch_byte(&mut cycles, memory);
-                    self.adc(value);
+        self.nmi_pending = false;
+        self.pending_interrupt = Some(Interrupt::Reset);
+        self.halt_state = None;
+
+        if self.legacy_reset {
+            self.accumulator = 0;
+            self.index_register_x = 0;
+            self.index_register_y = 0;
+            self.flags = CpuFlags::new();
+            self.program_counter = 0xFFFC;
+            self.stack_register = 0xFF;
+            self.pending_interrupt = None;
+        }
+    }
+
+    // the old reset that starts executing at $FFFC with no reset cycles, for
+    // tests that put their code on top of the vector
+    pub fn set_legacy_reset(&mut self, enabled: bool) {
+        self.legacy_reset = enabled;
+    }
+
+    // drives the IRQ input, the CPU keeps taking the interrupt for as long as
+    // the line is held and the I flag is clear
+    pub fn set_irq(&mut self, asserted: bool) {
+        self.irq_line = asserted;
+    }
+
+    // drives the NMI input, only the transition to asserted raises an NMI
+    pub fn set_nmi(&mut self, asserted: bool) {
+        if asserted && !self.nmi_line {
+            self.nmi_pending = true;
+        }
+        self.nmi_line = asserted;
+    }
+
+    pub fn snapshot(&self) -> CpuSnapshot {
+        CpuSnapshot {
+            program_counter: self.program_counter,
+            stack_register: self.stack_register,
+            accumulator: self.accumulator,
+            index_register_x: self.index_register_x,
+            index_register_y: self.index_register_y,
+            flags: self.flags,
+            irq_line: self.irq_line,
+            nmi_line: self.nmi_line,
+            nmi_pending: self.nmi_pending,
+            pending_interrupt: self.pending_interrupt,
+            halt_state: self.halt_state,
+            total_cycles: self.total_cycles,
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: &CpuSnapshot) {
+        self.program_counter = snapshot.program_counter;
+        self.stack_register = snapshot.stack_register;
+        self.accumulator = snapshot.accumulator;
+        self.index_register_x = snapshot.index_register_x;
+        self.index_register_y = snapshot.index_register_y;
+        self.flags = snapshot.flags;
+        self.irq_line = snapshot.irq_line;
+        self.nmi_line = snapshot.nmi_line;
+        self.nmi_pending = snapshot.nmi_pending;
+        self.pending_interrupt = snapshot.pending_interrupt;
+        self.halt_state = snapshot.halt_state;
+        self.total_cycles = snapshot.total_cycles;
+    }
+
+    pub fn set_invalid_opcode_policy(&mut self, policy: InvalidOpcodePolicy) {
+        self.invalid_opcode_policy = policy;
+    }
+
+    // off by default, so strict code still sees undocumented opcodes through
+    // the invalid opcode policy
+    pub fn set_undocumented_opcodes(&mut self, enabled: bool) {
+        self.undocumented_opcodes = enabled;
+    }
+
+    // ANE and LXA compute (A | magic) & ..., where the magic value depends on
+    // the chip and even its temperature. $EE is the common default, $FF and
+    // $00 are also seen on real parts
+    pub fn set_magic_constant(&mut self, value: Byte) {
+        self.magic_constant = value;
+    }
+
+    // runs whole instructions until at least `cycles` have gone by. the last
+    // one may run past the budget, Ok holds by how many cycles so the caller
+    // can take them off the next slice. a halted CPU that nothing in this
+    // slice can wake skips straight to the end of it, so a run loop can drive
+    // IRQ and NMI between slices and fast-forward through WAI
+    pub fn execute<B: Bus>(&mut self, memory: &mut B, cycles: u32) -> Result<u32, CpuError> {
+        // counted in u64 so a budget near u32::MAX cannot wrap on the last step
+        let budget = cycles as u64;
+        let mut elapsed: u64 = 0;
+        while elapsed < budget {
+            if self.halt_state.is_some() && !self.wakes_up() {
+                self.add_cycles((budget - elapsed) as u32, memory);
+                return Ok(0);
+            }
+            elapsed += self.step(memory)?.cycles as u64;
+        }
+        Ok((elapsed - budget) as u32)
+    }
+
+    // runs exactly one instruction, or one reset/interrupt sequence if one
+    // is due
+    pub fn step<B: Bus>(&mut self, memory: &mut B) -> Result<StepResult, CpuError> {
+        if self.halt_state.is_some() && self.wakes_up() {
+            self.halt_state = None;
+            self.poll_interrupts();
+        }
+
+        if let Some(halt_state) = self.halt_state {
+            self.add_cycles(1, memory);
+            return Ok(StepResult {
+                cycles: 1,
+                opcode: None,
+                interrupt: None,
+                halted: Some(halt_state),
+            });
+        }
+
+        if let Some(interrupt) = self.pending_interrupt.take() {
+            // two dummy reads of the next opcode, then the same push and
+            // vector fetch BRK does
+            let hijacked = match interrupt {
+                Interrupt::Reset => {
+                    self.reset_sequence(memory);
+                    false
                 }
-                Ok(Opcode::AdcZp) => {
-                    let addr = self.fetch_byte(&mut cycles, memory);
-                    let value = self.read_byte(addr, &mut cycles, memory);
-                    self.adc(value);
+                Interrupt::Irq => self.interrupt(IRQ_VECTOR, false, memory),
+                Interrupt::Nmi => self.interrupt(NMI_VECTOR, false, memory),
+            };
+            self.add_cycles(INTERRUPT_CYCLES, memory);
+            return Ok(StepResult {
+                cycles: INTERRUPT_CYCLES,
+                opcode: None,
+                interrupt: Some(if hijacked { Interrupt::Nmi } else { interrupt }),
+                halted: None,
+            });
+        }
+
+        let opcode = self.fetch_byte(memory);
+        let mut interrupt = None;
+
+        let instruction = self
+            .variant
+            .decode(opcode)
+            .filter(|instruction| self.runs(*instruction));
+
+        let cycles = match instruction {
+            Some(instruction) => {
+                let mut cycles = instruction.cycles as u32;
+                if self.execute_instruction(instruction, &mut cycles, memory) {
+                    interrupt = Some(Interrupt::Nmi);
                 }
-                Ok(Opcode::AdcZpx) => {
-                    let mut addr = self.fetch_byte(&mut cycles, memory);
-                    addr += self.index_register_x;
-                    cycles -= 1;
-                    let value = self.read_byte(addr, &mut cycles, memory);
-                    self.adc(value);
+                cycles
+            }
+            None => {
+                let opcode_addr = self.program_counter.wrapping_sub(1);
+                if let Err(error) = self.invalid_opcode(opcode, opcode_addr) {
+                    // the opcode fetch still happened
+                    self.add_cycles(1, memory);
+                    return Err(error);
                 }
-                Ok(Opcode::AdcAbs) => {
-                    let addr = self.fetch_word(&mut cycles, memory);
-                    self.adc(memory[addr as usize]);
-                    cycles -= 1;
+                2
+            }
+        };
+
+        if self.halt_state.is_none() {
+            self.poll_interrupts();
+        }
+        self.add_cycles(cycles, memory);
+
+        Ok(StepResult {
+            cycles,
+            opcode: Some(opcode),
+            interrupt,
+            halted: self.halt_state,
+        })
+    }
+
+    // runs a decoded instruction whose opcode has already been fetched.
+    // `cycles` starts at the table's base count and picks up page crossings
+    // and taken branches. returns true when an NMI hijacked BRK
+    fn execute_instruction<B: Bus>(
+        &mut self,
+        instruction: Instruction,
+        cycles: &mut u32,
+        memory: &mut B,
+    ) -> bool {
+        match instruction.mnemonic {
+            Mnemonic::Lda => {
+                self.accumulator = self.read_operand(instruction, cycles, memory);
+                self.set_zero_negative(self.accumulator);
+            }
+            Mnemonic::Ldx => {
+                self.index_register_x = self.read_operand(instruction, cycles, memory);
+                self.set_zero_negative(self.index_register_x);
+            }
+            Mnemonic::Ldy => {
+                self.index_register_y = self.read_operand(instruction, cycles, memory);
+                self.set_zero_negative(self.index_register_y);
+            }
+            Mnemonic::Sta => self.write_operand(instruction, self.accumulator, cycles, memory),
+            Mnemonic::Stx => self.write_operand(instruction, self.index_register_x, cycles, memory),
+            Mnemonic::Sty => self.write_operand(instruction, self.index_register_y, cycles, memory),
+            Mnemonic::Tax => {
+                self.index_register_x = self.accumulator;
+                self.set_zero_negative(self.index_register_x);
+            }
+            Mnemonic::Tay => {
+                self.index_register_y = self.accumulator;
+                self.set_zero_negative(self.index_register_y);
+            }
+            Mnemonic::Txa => {
+                self.accumulator = self.index_register_x;
+                self.set_zero_negative(self.accumulator);
+            }
+            Mnemonic::Tya => {
+                self.accumulator = self.index_register_y;
+                self.set_zero_negative(self.accumulator);
+            }
+            Mnemonic::Tsx => {
+                self.index_register_x = self.stack_register;
+                self.set_zero_negative(self.index_register_x);
+            }
+            // the only transfer that leaves the flags alone
+            Mnemonic::Txs => self.stack_register = self.index_register_x,
+            Mnemonic::Pha => self.push_byte(self.accumulator, memory),
+            Mnemonic::Pla => {
+                self.accumulator = self.pull_byte(memory);
+                self.set_zero_negative(self.accumulator);
+            }
+            Mnemonic::Php => {
+                // B and the unused bit only exist in the pushed copy
+                let status = self.flags.with_break_command(true).with_unused(true);
+                self.push_byte(status.into_bytes()[0], memory);
+            }
+            Mnemonic::Plp => {
+                let status = self.pull_byte(memory);
+                self.set_status_from_stack(status);
+            }
+            Mnemonic::Adc => {
+                let value = self.read_operand(instruction, cycles, memory);
+                self.adc(value);
+                *cycles += self.decimal_penalty();
+            }
+            Mnemonic::Sbc => {
+                let value = self.read_operand(instruction, cycles, memory);
+                self.sbc(value);
+                *cycles += self.decimal_penalty();
+            }
+            Mnemonic::And => {
+                let value = self.read_operand(instruction, cycles, memory);
+                self.and(value);
+            }
+            Mnemonic::Ora => {
+                let value = self.read_operand(instruction, cycles, memory);
+                self.ora(value);
+            }
+            Mnemonic::Eor => {
+                let value = self.read_operand(instruction, cycles, memory);
+                self.eor(value);
+            }
+            Mnemonic::Bit => {
+                let value = self.read_operand(instruction, cycles, memory);
+                if instruction.mode == AddressingMode::Immediate {
+                    // there is no memory to copy N and V from
+                    self.flags.set_zero(self.accumulator & value == 0);
+                } else {
+                    self.bit(value);
                 }
-                Ok(Opcode::AdcAbsX) => {
-                    let base_addr = self.fetch_word(&mut cycles, memory);
-                    let addr = base_addr + self.index_register_x as u16;
-
-                    let page_crossed =
-                        (base_addr & 0xFF00) != ((base_addr + self.index_register_x) & 0xFF00);
-                    cycles -= 2;
-                    if page_crossed {
-                        cycles -= 1;
-                    }
-
-                    self.adc(addr);
+            }
+            Mnemonic::Cmp => {
+                let value = self.read_operand(instruction, cycles, memory);
+                self.compare(self.accumulator, value);
+            }
+            Mnemonic::Cpx => {
+                let value = self.read_operand(instruction, cycles, memory);
+                self.compare(self.index_register_x, value);
+            }
+            Mnemonic::Cpy => {
+                let value = self.read_operand(instruction, cycles, memory);
+                self.compare(self.index_register_y, value);
+            }
+            Mnemonic::Inx => {
+                self.index_register_x = self.index_register_x.wrapping_add(1);
+                self.set_zero_negative(self.index_register_x);
+            }
+            Mnemonic::Iny => {
+                self.index_register_y = self.index_register_y.wrapping_add(1);
+                self.set_zero_negative(self.index_register_y);
+            }
+            Mnemonic::Dex => {
+                self.index_register_x = self.index_register_x.wrapping_sub(1);
+                self.set_zero_negative(self.index_register_x);
+            }
+            Mnemonic::Dey => {
+                self.index_register_y = self.index_register_y.wrapping_sub(1);
+                self.set_zero_negative(self.index_register_y);
+            }
+            Mnemonic::Inc => {
+                self.modify_operand(instruction, Self::inc, cycles, memory);
+            }
+            Mnemonic::Dec => {
+                self.modify_operand(instruction, Self::dec, cycles, memory);
+            }
+            Mnemonic::Asl => {
+                self.modify_operand(instruction, Self::asl, cycles, memory);
+            }
+            Mnemonic::Lsr => {
+                self.modify_operand(instruction, Self::lsr, cycles, memory);
+            }
+            Mnemonic::Rol => {
+                self.modify_operand(instruction, Self::rol, cycles, memory);
+            }
+            Mnemonic::Ror => {
+                self.modify_operand(instruction, Self::ror, cycles, memory);
+            }
+            Mnemonic::Clc => self.flags.set_carry(false),
+            Mnemonic::Sec => self.flags.set_carry(true),
+            Mnemonic::Cli => self.flags.set_interrupt_disable(false),
+            Mnemonic::Sei => self.flags.set_interrupt_disable(true),
+            Mnemonic::Cld => self.flags.set_decimal(false),
+            Mnemonic::Sed => self.flags.set_decimal(true),
+            Mnemonic::Clv => self.flags.set_overflow(false),
+            Mnemonic::Nop => {
+                // the undocumented forms still fetch and read their operand
+                if instruction.mode != AddressingMode::Implied {
+                    self.read_operand(instruction, cycles, memory);
                 }
+            }
+            Mnemonic::Jsr => {
+                let sub_addr = self.fetch_word(memory);
 
-                Ok(Opcode::Jsr) => {
-                    let sub_addr = self.fetch_word(&mut cycles, memory);
+                // the pushed address is the last byte of the JSR itself,
+                // RTS adds the missing one back
+                let return_addr = self.program_counter.wrapping_sub(1);
+                self.push_word(return_addr, memory);
 
-                    //TODO: return addr doesnt seem to be returning right
-                    let return_addr = self.program_counter.wrapping_sub(2);
+                self.program_counter = sub_addr;
+            }
+            Mnemonic::Rts => {
+                let return_addr = self.pull_word(memory);
+                self.program_counter = return_addr.wrapping_add(1);
+            }
+            Mnemonic::Rti => {
+                let status = self.pull_byte(memory);
+                self.set_status_from_stack(status);
+                self.program_counter = self.pull_word(memory);
+            }
+            Mnemonic::Jmp => {
+                self.program_counter = self.operand_address(instruction, cycles, memory);
+            }
+            Mnemonic::Bpl => self.branch(!self.flags.negative(), cycles, memory),
+            Mnemonic::Bmi => self.branch(self.flags.negative(), cycles, memory),
+            Mnemonic::Bvc => self.branch(!self.flags.overflow(), cycles, memory),
+            Mnemonic::Bvs => self.branch(self.flags.overflow(), cycles, memory),
+            Mnemonic::Bcc => self.branch(!self.flags.carry(), cycles, memory),
+            Mnemonic::Bcs => self.branch(self.flags.carry(), cycles, memory),
+            Mnemonic::Bne => self.branch(!self.flags.zero(), cycles, memory),
+            Mnemonic::Beq => self.branch(self.flags.zero(), cycles, memory),
+            Mnemonic::Brk => {
+                // BRK skips a padding byte, so the handler returns to PC + 2
+                self.program_counter = self.program_counter.wrapping_add(1);
+                return self.interrupt(IRQ_VECTOR, true, memory);
+            }
 
-                    memory[self.stack_register as usize] = (return_addr >> 8 & 0xFF) as Byte;
-                    self.stack_register -= 1;
-                    cycles -= 2;
+            Mnemonic::Slo => {
+                let value = self.modify_operand(instruction, Self::asl, cycles, memory);
+                self.ora(value);
+            }
+            Mnemonic::Rla => {
+                let value = self.modify_operand(instruction, Self::rol, cycles, memory);
+                self.and(value);
+            }
+            Mnemonic::Sre => {
+                let value = self.modify_operand(instruction, Self::lsr, cycles, memory);
+                self.eor(value);
+            }
+            Mnemonic::Rra => {
+                // the carry out of ROR is the carry into ADC
+                let value = self.modify_operand(instruction, Self::ror, cycles, memory);
+                self.adc(value);
+            }
+            Mnemonic::Dcp => {
+                let value = self.modify_operand(instruction, Self::dec, cycles, memory);
+                self.compare(self.accumulator, value);
+            }
+            Mnemonic::Isc => {
+                let value = self.modify_operand(instruction, Self::inc, cycles, memory);
+                self.sbc(value);
+            }
+            Mnemonic::Sax => {
+                // A and X are both driven onto the bus, no flags change
+                let value = self.accumulator & self.index_register_x;
+                self.write_operand(instruction, value, cycles, memory);
+            }
+            Mnemonic::Lax => {
+                let value = self.read_operand(instruction, cycles, memory);
+                self.accumulator = value;
+                self.index_register_x = value;
+                self.set_zero_negative(value);
+            }
+            Mnemonic::Anc => {
+                // AND, then C is a copy of N as if the result had been shifted
+                let value = self.read_operand(instruction, cycles, memory);
+                self.and(value);
+                self.flags.set_carry(self.flags.negative());
+            }
+            Mnemonic::Alr => {
+                let value = self.read_operand(instruction, cycles, memory);
+                self.and(value);
+                self.accumulator = self.lsr(self.accumulator);
+            }
+            Mnemonic::Arr => {
+                let value = self.read_operand(instruction, cycles, memory);
+                self.arr(value);
+            }
+            Mnemonic::Sbx => {
+                // X = (A & X) - M with CMP's flags, ignoring carry and D
+                let value = self.read_operand(instruction, cycles, memory);
+                let register = self.accumulator & self.index_register_x;
+                self.compare(register, value);
+                self.index_register_x = register.wrapping_sub(value);
+            }
 
-                    memory[self.stack_register as usize] = (return_addr & 0xFF) as Byte;
-                    self.stack_register -= 1;
-                    cycles -= 1;
+            Mnemonic::Ane => {
+                let value = self.read_operand(instruction, cycles, memory);
+                self.accumulator =
+                    (self.accumulator | self.magic_constant) & self.index_register_x & value;
+                self.set_zero_negative(self.accumulator);
+            }
+            Mnemonic::Lxa => {
+                let value = self.read_operand(instruction, cycles, memory);
+                self.accumulator = (self.accumulator | self.magic_constant) & value;
+                self.index_register_x = self.accumulator;
+                self.set_zero_negative(self.accumulator);
+            }
+            Mnemonic::Las => {
+                let value = self.read_operand(instruction, cycles, memory) & self.stack_register;
+                self.accumulator = value;
+                self.index_register_x = value;
+                self.stack_register = value;
+                self.set_zero_negative(value);
+            }
+            Mnemonic::Sha => {
+                let value = self.accumulator & self.index_register_x;
+                self.write_operand_and_high(instruction, value, cycles, memory);
+            }
+            Mnemonic::Shx => {
+                self.write_operand_and_high(instruction, self.index_register_x, cycles, memory);
+            }
+            Mnemonic::Shy => {
+                self.write_operand_and_high(instruction, self.index_register_y, cycles, memory);
+            }
+            Mnemonic::Tas => {
+                self.stack_register = self.accumulator & self.index_register_x;
+                self.write_operand_and_high(instruction, self.stack_register, cycles, memory);
+            }
+            Mnemonic::Bra => self.branch(true, cycles, memory),
+            Mnemonic::Phx => self.push_byte(self.index_register_x, memory),
+            Mnemonic::Phy => self.push_byte(self.index_register_y, memory),
+            Mnemonic::Plx => {
+                self.index_register_x = self.pull_byte(memory);
+                self.set_zero_negative(self.index_register_x);
+            }
+            Mnemonic::Ply => {
+                self.index_register_y = self.pull_byte(memory);
+                self.set_zero_negative(self.index_register_y);
+            }
+            Mnemonic::Stz => self.write_operand(instruction, 0, cycles, memory),
+            Mnemonic::Tsb => {
+                self.modify_operand(instruction, Self::tsb, cycles, memory);
+            }
+            Mnemonic::Trb => {
+                self.modify_operand(instruction, Self::trb, cycles, memory);
+            }
+            Mnemonic::Rmb0
+            | Mnemonic::Rmb1
+            | Mnemonic::Rmb2
+            | Mnemonic::Rmb3
+            | Mnemonic::Rmb4
+            | Mnemonic::Rmb5
+            | Mnemonic::Rmb6
+            | Mnemonic::Rmb7 => {
+                let mask = self.bit_mask(instruction);
+                self.modify_operand(instruction, |_, value| value & !mask, cycles, memory);
+            }
+            Mnemonic::Smb0
+            | Mnemonic::Smb1
+            | Mnemonic::Smb2
+            | Mnemonic::Smb3
+            | Mnemonic::Smb4
+            | Mnemonic::Smb5
+            | Mnemonic::Smb6
+            | Mnemonic::Smb7 => {
+                let mask = self.bit_mask(instruction);
+                self.modify_operand(instruction, |_, value| value | mask, cycles, memory);
+            }
+            Mnemonic::Bbr0
+            | Mnemonic::Bbr1
+            | Mnemonic::Bbr2
+            | Mnemonic::Bbr3
+            | Mnemonic::Bbr4
+            | Mnemonic::Bbr5
+            | Mnemonic::Bbr6
+            | Mnemonic::Bbr7 => {
+                let mask = self.bit_mask(instruction);
+                let zero_page_addr = self.fetch_byte(memory);
+                let value = memory.read(zero_page_addr as Word);
+                self.branch(value & mask == 0, cycles, memory);
+            }
+            Mnemonic::Bbs0
+            | Mnemonic::Bbs1
+            | Mnemonic::Bbs2
+            | Mnemonic::Bbs3
+            | Mnemonic::Bbs4
+            | Mnemonic::Bbs5
+            | Mnemonic::Bbs6
+            | Mnemonic::Bbs7 => {
+                let mask = self.bit_mask(instruction);
+                let zero_page_addr = self.fetch_byte(memory);
+                let value = memory.read(zero_page_addr as Word);
+                self.branch(value & mask != 0, cycles, memory);
+            }
+            Mnemonic::Wai => self.halt_state = Some(HaltState::Waiting),
+            Mnemonic::Stp => self.halt_state = Some(HaltState::Stopped),
+            Mnemonic::Jam => {
+                // PC stays on the JAM byte, as if it were fetched forever
+                self.program_counter = self.program_counter.wrapping_sub(1);
+                self.halt_state = Some(HaltState::Jammed {
+                    program_counter: self.program_counter,
+                });
+            }
+        }
+        false
+    }
 
-                    self.program_counter = sub_addr;
-                }
+    // the single bit RMB, SMB, BBR and BBS work on
+    fn bit_mask(&self, instruction: Instruction) -> Byte {
+        1 << instruction.mnemonic.bit().expect("not a bit instruction")
+    }
 
-                Err(_) => {
-                    eprintln!("Invalid instruction byte: {:02X}", instruction);
-                }
-            }
+    // only a waiting CPU can be woken, by an NMI edge or the IRQ line. the I
+    // flag decides whether the IRQ is then taken, not whether the CPU wakes
+    fn wakes_up(&self) -> bool {
+        self.halt_state == Some(HaltState::Waiting) && (self.nmi_pending || self.irq_line)
+    }
+
+    // undocumented NMOS opcodes are opt-in. the 65C02 defines all of its
+    // reserved opcodes as NOPs, so there is nothing to trap
+    fn runs(&self, instruction: Instruction) -> bool {
+        !instruction.undocumented || self.undocumented_opcodes || self.variant.is_cmos()
+    }
+
+    // NOP and a trap that returns Ok carry on as if the byte were a one-byte,
+    // two-cycle NOP. halting leaves PC on the offending byte
+    fn invalid_opcode(&mut self, opcode: Byte, opcode_addr: Word) -> Result<(), CpuError> {
+        let error = CpuError::InvalidOpcode {
+            opcode,
+            program_counter: opcode_addr,
+        };
+
+        let result = match &mut self.invalid_opcode_policy {
+            InvalidOpcodePolicy::Halt => Err(error),
+            InvalidOpcodePolicy::Nop => Ok(()),
+            InvalidOpcodePolicy::Trap(callback) => callback(error),
+        };
+        if result.is_err() {
+            self.program_counter = opcode_addr;
         }
+        result
     }
 
-    fn fetch_byte(&mut self, cycles: &mut u32, memory: &mut Mem) -> Byte {
-        let data = memory[self.program_counter as usize];
-        self.program_counter += 1;
-        *cycles -= 1;
-        data
+    fn add_cycles<B: Bus>(&mut self, cycles: u32, memory: &mut B) {
+        self.total_cycles += cycles as u64;
+        memory.sync(self.total_cycles);
+    }
+
+    fn poll_interrupts(&mut self) {
+        if self.nmi_pending {
+            self.nmi_pending = false;
+            self.pending_interrupt = Some(Interrupt::Nmi);
+        } else if self.irq_line && !self.flags.interrupt_disable() {
+            self.pending_interrupt = Some(Interrupt::Irq);
+        }
+    }
+
+    // reset runs the interrupt sequence with the writes turned into reads, so
+    // SP still drops by three but nothing lands on the stack. A, X, Y and the
+    // other flags keep whatever they held, except D on the 65C02
+    fn reset_sequence<B: Bus>(&mut self, memory: &mut B) {
+        self.stack_register = self.stack_register.wrapping_sub(3);
+        self.flags.set_interrupt_disable(true);
+        if self.variant.is_cmos() {
+            self.flags.set_decimal(false);
+        }
+
+        self.program_counter = self.read_word(RESET_VECTOR, memory);
     }
 
-    fn fetch_word(&mut self, cycles: &mut u32, memory: &mut Mem) -> Word {
-        let mut data: Word = memory[self.program_counter as usize] as Word;
-        self.program_counter += 1;
-        *cycles -= 1;
+    // shared tail of BRK, IRQ and NMI: push PC and status, set I, then load
+    // PC from the vector. on the NMOS part an NMI that turns up before the
+    // vector is read hijacks the sequence, even for BRK. returns true when
+    // that happened. the 65C02 finishes the sequence it started and also
+    // clears D, so handlers always start in binary mode
+    fn interrupt<B: Bus>(&mut self, vector: Word, break_flag: bool, memory: &mut B) -> bool {
+        self.push_word(self.program_counter, memory);
+        let status = self.flags.with_break_command(break_flag).with_unused(true);
+        self.push_byte(status.into_bytes()[0], memory);
+        self.flags.set_interrupt_disable(true);
+
+        let cmos = self.variant.is_cmos();
+        if cmos {
+            self.flags.set_decimal(false);
+        }
 
-        data |= (memory[self.program_counter as usize] as Word) << 8;
-        self.program_counter += 1;
-        *cycles -= 1;
+        let hijacked = !cmos && self.nmi_pending && vector != NMI_VECTOR;
+        if hijacked {
+            self.nmi_pending = false;
+        }
+        let vector = if hijacked { NMI_VECTOR } else { vector };
+        self.program_counter = self.read_word(vector, memory);
+        hijacked
+    }
+
+    // PC wraps from $FFFF to $0000 like the real address bus
+    fn fetch_byte<B: Bus>(&mut self, memory: &mut B) -> Byte {
+        let data = memory.read(self.program_counter);
+        self.program_counter = self.program_counter.wrapping_add(1);
         data
     }
 
-    fn read_byte(&mut self, addr: Byte, cycles: &mut u32, memory: &mut Mem) -> Byte {
-        *cycles -= 1;
-        memory[addr as usize]
+    fn fetch_word<B: Bus>(&mut self, memory: &mut B) -> Word {
+        let low_byte = self.fetch_byte(memory) as Word;
+        let high_byte = self.fetch_byte(memory) as Word;
+        low_byte | (high_byte << 8)
     }
 
-    fn lda_set_status(&mut self) {
-        self.flags.set_zero(self.accumulator == 0);
-        self.flags
-            .set_negative((self.accumulator & 0b10000000) != 0);
+    fn read_word<B: Bus>(&mut self, addr: Word, memory: &mut B) -> Word {
+        let low_byte = memory.read(addr) as Word;
+        let high_byte = memory.read(addr.wrapping_add(1)) as Word;
+        low_byte | (high_byte << 8)
+    }
+
+    // pointers in zero page never leave it, a pointer at $FF takes its high
+    // byte from $00 rather than $0100
+    fn read_word_zero_page<B: Bus>(&mut self, addr: Byte, memory: &mut B) -> Word {
+        let low_byte = memory.read(addr as Word) as Word;
+        let high_byte = memory.read(addr.wrapping_add(1) as Word) as Word;
+        low_byte | (high_byte << 8)
+    }
+
+    // fetches the operand of any mode that names a memory location and
+    // returns the effective address. immediate operands live at PC, so their
+    // address is just the operand byte itself. indexed reads pay for a page
+    // cross here when the table says they do
+    fn operand_address<B: Bus>(
+        &mut self,
+        instruction: Instruction,
+        cycles: &mut u32,
+        memory: &mut B,
+    ) -> Word {
+        let (addr, page_crossed) = match instruction.mode {
+            AddressingMode::Immediate => {
+                let addr = self.program_counter;
+                self.program_counter = self.program_counter.wrapping_add(1);
+                (addr, false)
+            }
+            AddressingMode::ZeroPage => (self.fetch_byte(memory) as Word, false),
+            AddressingMode::ZeroPageX => {
+                let zero_page_addr = self.fetch_byte(memory);
+                (
+                    zero_page_addr.wrapping_add(self.index_register_x) as Word,
+                    false,
+                )
+            }
+            AddressingMode::ZeroPageY => {
+                let zero_page_addr = self.fetch_byte(memory);
+                (
+                    zero_page_addr.wrapping_add(self.index_register_y) as Word,
+                    false,
+                )
+            }
+            AddressingMode::Absolute => (self.fetch_word(memory), false),
+            AddressingMode::AbsoluteX => {
+                let base_addr = self.fetch_word(memory);
+                add_index(base_addr, self.index_register_x)
+            }
+            AddressingMode::AbsoluteY => {
+                let base_addr = self.fetch_word(memory);
+                add_index(base_addr, self.index_register_y)
+            }
+            AddressingMode::Indirect => {
+                let pointer = self.fetch_word(memory);
+
+                // the NMOS 6502 never carries into the high byte of the
+                // pointer, so JMP ($10FF) reads $10FF and $1000
+                let pointer_high = if self.variant.is_cmos() {
+                    pointer.wrapping_add(1)
+                } else {
+                    (pointer & 0xFF00) | (pointer.wrapping_add(1) & 0x00FF)
+                };
+                let target_low = memory.read(pointer) as Word;
+                let target_high = memory.read(pointer_high) as Word;
+                (target_low | (target_high << 8), false)
+            }
+            AddressingMode::AbsoluteIndexedIndirect => {
+                let pointer = self
+                    .fetch_word(memory)
+                    .wrapping_add(self.index_register_x as Word);
+                (self.read_word(pointer, memory), false)
+            }
+            AddressingMode::ZeroPageIndirect => {
+                let pointer = self.fetch_byte(memory);
+                (self.read_word_zero_page(pointer, memory), false)
+            }
+            AddressingMode::IndirectX => {
+                let zero_page_addr = self.fetch_byte(memory);
+                let pointer = zero_page_addr.wrapping_add(self.index_register_x);
+                (self.read_word_zero_page(pointer, memory), false)
+            }
+            AddressingMode::IndirectY => {
+                let pointer = self.fetch_byte(memory);
+                let base_addr = self.read_word_zero_page(pointer, memory);
+                add_index(base_addr, self.index_register_y)
+            }
+            AddressingMode::Implied
+            | AddressingMode::Accumulator
+            | AddressingMode::Relative
+            | AddressingMode::ZeroPageRelative => {
+                unreachable!("{:?} has no operand address", instruction.mode)
+            }
+        };
+
+        if page_crossed && instruction.page_penalty {
+            *cycles += 1;
+        }
+        addr
+    }
+
+    fn read_operand<B: Bus>(
+        &mut self,
+        instruction: Instruction,
+        cycles: &mut u32,
+        memory: &mut B,
+    ) -> Byte {
+        let addr = self.operand_address(instruction, cycles, memory);
+        memory.read(addr)
+    }
+
+    fn write_operand<B: Bus>(
+        &mut self,
+        instruction: Instruction,
+        value: Byte,
+        cycles: &mut u32,
+        memory: &mut B,
+    ) {
+        let addr = self.operand_address(instruction, cycles, memory);
+        memory.write(addr, value);
+    }
+
+    // SHA, SHX, SHY and TAS store `value` & (high byte of the base address
+    // + 1). when the index crosses a page the carry never makes it into the
+    // address, the stored value takes the place of the high byte instead
+    fn write_operand_and_high<B: Bus>(
+        &mut self,
+        instruction: Instruction,
+        value: Byte,
+        cycles: &mut u32,
+        memory: &mut B,
+    ) {
+        let index = match instruction.mode {
+            AddressingMode::AbsoluteX => self.index_register_x,
+            _ => self.index_register_y,
+        };
+        let addr = self.operand_address(instruction, cycles, memory);
+        let base_addr = addr.wrapping_sub(index as Word);
+
+        let value = value & ((base_addr >> 8) as Byte).wrapping_add(1);
+        let addr = if (base_addr & 0xFF00) != (addr & 0xFF00) {
+            (addr & 0x00FF) | ((value as Word) << 8)
+        } else {
+            addr
+        };
+        memory.write(addr, value);
+    }
+
+    // the read-modify-write instructions: shifts, rotates, INC and DEC work
+    // on A or on memory, TSB, TRB, RMB and SMB on memory, and all return the
+    // result. the NMOS 6502 writes the unmodified value back before the
+    // result, and memory-mapped registers see both writes. the 65C02 reads
+    // the location a second time instead
+    fn modify_operand<B: Bus>(
+        &mut self,
+        instruction: Instruction,
+        operation: impl FnOnce(&mut Self, Byte) -> Byte,
+        cycles: &mut u32,
+        memory: &mut B,
+    ) -> Byte {
+        if instruction.mode == AddressingMode::Accumulator {
+            self.accumulator = operation(self, self.accumulator);
+            return self.accumulator;
+        }
+
+        let addr = self.operand_address(instruction, cycles, memory);
+        let value = memory.read(addr);
+        if self.variant.is_cmos() {
+            memory.read(addr);
+        } else {
+            memory.write(addr, value);
+        }
+        let result = operation(self, value);
+        memory.write(addr, result);
+        result
+    }
+
+    // the offset is signed and relative to the instruction after the branch.
+    // taking the branch costs a cycle, landing on another page costs one more
+    fn branch<B: Bus>(&mut self, condition: bool, cycles: &mut u32, memory: &mut B) {
+        let offset = self.fetch_byte(memory) as i8;
+        if !condition {
+            return;
+        }
+
+        let target = self.program_counter.wrapping_add_signed(offset as i16);
+        *cycles += 1;
+        if (self.program_counter & 0xFF00) != (target & 0xFF00) {
+            *cycles += 1;
+        }
+        self.program_counter = target;
+    }
+
+    // the stack grows downwards from $01FF: push writes then decrements, pull
+    // increments then reads, and SP wraps within page $01 either way
+    fn push_byte<B: Bus>(&mut self, value: Byte, memory: &mut B) {
+        memory.write(STACK_BASE | self.stack_register as Word, value);
+        self.stack_register = self.stack_register.wrapping_sub(1);
+    }
+
+    fn pull_byte<B: Bus>(&mut self, memory: &mut B) -> Byte {
+        self.stack_register = self.stack_register.wrapping_add(1);
+        memory.read(STACK_BASE | self.stack_register as Word)
+    }
+
+    fn push_word<B: Bus>(&mut self, value: Word, memory: &mut B) {
+        self.push_byte((value >> 8) as Byte, memory);
+        self.push_byte((value & 0xFF) as Byte, memory);
+    }
+
+    fn pull_word<B: Bus>(&mut self, memory: &mut B) -> Word {
+        let low_byte = self.pull_byte(memory) as Word;
+        let high_byte = self.pull_byte(memory) as Word;
+        low_byte | (high_byte << 8)
+    }
+
+    // B and the unused bit are not real latches, so a status byte pulled off
+    // the stack never changes them
+    fn set_status_from_stack(&mut self, value: Byte) {
+        let pulled = CpuFlags::from_bytes([value]);
+        self.flags = pulled
+            .with_break_command(self.flags.break_command())
+            .with_unused(self.flags.unused());
+    }
+
+    fn set_zero_negative(&mut self, value: Byte) {
+        self.flags.set_zero(value == 0);
+        self.flags.set_negative((value & 0b10000000) != 0);
+    }
+
+    fn and(&mut self, value: Byte) {
+        self.accumulator &= value;
+        self.set_zero_negative(self.accumulator);
+    }
+
+    fn ora(&mut self, value: Byte) {
+        self.accumulator |= value;
+        self.set_zero_negative(self.accumulator);
     }
 
+    fn eor(&mut self, value: Byte) {
+        self.accumulator ^= value;
+        self.set_zero_negative(self.accumulator);
+    }
+
+    // Z comes from A & M, but N and V are copied straight from bits 7 and 6
+    // of memory
+    fn bit(&mut self, value: Byte) {
+        self.flags.set_zero(self.accumulator & value == 0);
+        self.flags.set_negative(value & 0b10000000 != 0);
+        self.flags.set_overflow(value & 0b01000000 != 0);
+    }
+
+    fn inc(&mut self, value: Byte) -> Byte {
+        let result = value.wrapping_add(1);
+        self.set_zero_negative(result);
+        result
+    }
+
+    fn dec(&mut self, value: Byte) -> Byte {
+        let result = value.wrapping_sub(1);
+        self.set_zero_negative(result);
+        result
+    }
+
+    // TSB and TRB set Z from A & M like BIT, then set or clear A's bits in M
+    fn tsb(&mut self, value: Byte) -> Byte {
+        self.flags.set_zero(self.accumulator & value == 0);
+        value | self.accumulator
+    }
+
+    fn trb(&mut self, value: Byte) -> Byte {
+        self.flags.set_zero(self.accumulator & value == 0);
+        value & !self.accumulator
+    }
+
+    fn asl(&mut self, value: Byte) -> Byte {
+        let result = value << 1;
+        self.flags.set_carry(value & 0b10000000 != 0);
+        self.set_zero_negative(result);
+        result
+    }
+
+    fn lsr(&mut self, value: Byte) -> Byte {
+        let result = value >> 1;
+        self.flags.set_carry(value & 0b00000001 != 0);
+        self.set_zero_negative(result);
+        result
+    }
+
+    fn rol(&mut self, value: Byte) -> Byte {
+        let result = (value << 1) | self.flags.carry() as Byte;
+        self.flags.set_carry(value & 0b10000000 != 0);
+        self.set_zero_negative(result);
+        result
+    }
+
+    fn ror(&mut self, value: Byte) -> Byte {
+        let result = (value >> 1) | ((self.flags.carry() as Byte) << 7);
+        self.flags.set_carry(value & 0b00000001 != 0);
+        self.set_zero_negative(result);
+        result
+    }
+
+    // CMP, CPX and CPY: C is set when register >= value (no borrow), Z and N
+    // come from the subtraction, which is thrown away
+    fn compare(&mut self, register: Byte, value: Byte) {
+        self.flags.set_carry(register >= value);
+        self.set_zero_negative(register.wrapping_sub(value));
+    }
+
+    // AND then ROR A, but the flags come from the adder: V is bit 6 xor bit 5
+    // of the result and C is bit 6. in decimal mode each nibble of the AND
+    // gets a BCD fixup the way ADC would apply it
+    fn arr(&mut self, value: Byte) {
+        let and = self.accumulator & value;
+        let mut result = (and >> 1) | ((self.flags.carry() as Byte) << 7);
+
+        if !self.decimal_mode() {
+            self.set_zero_negative(result);
+            self.flags.set_carry(result & 0b01000000 != 0);
+            self.flags
+                .set_overflow(((result >> 6) ^ (result >> 5)) & 0b00000001 != 0);
+            self.accumulator = result;
+            return;
+        }
+
+        // N comes from the old carry, Z and V from the unadjusted result
+        self.set_zero_negative(result);
+        self.flags.set_overflow((and ^ result) & 0b01000000 != 0);
+
+        let low = and & 0x0F;
+        if low + (low & 0x01) > 0x05 {
+            result = (result & 0xF0) | (result.wrapping_add(0x06) & 0x0F);
+        }
+        let high = and >> 4;
+        let carry = high + (high & 0x01) > 0x05;
+        if carry {
+            result = result.wrapping_add(0x60);
+        }
+        self.flags.set_carry(carry);
+        self.accumulator = result;
+    }
+
+    // D is set and the chip has a decimal adder to go with it
+    fn decimal_mode(&self) -> bool {
+        self.flags.decimal() && self.variant.has_decimal_mode()
+    }
+
+    // the 65C02 spends an extra cycle on ADC and SBC in decimal mode, which
+    // is where its N and Z fixup happens
+    fn decimal_penalty(&self) -> u32 {
+        (self.variant.is_cmos() && self.decimal_mode()) as u32
+    }
+
+    // the 65C02 computes the same accumulator and C and V as the NMOS part,
+    // but takes N and Z from the decimal result
     fn adc(&mut self, value: Byte) {
+        if self.decimal_mode() {
+            self.adc_decimal(value);
+            if self.variant.is_cmos() {
+                self.set_zero_negative(self.accumulator);
+            }
+        } else {
+            self.adc_binary(value);
+        }
+    }
+
+    // in binary mode A - M - (1 - C) is the same as A + !M + C, so the carry
+    // acts as an inverted borrow and V falls out of the adc logic for free.
+    // in decimal mode the NMOS part still sets every flag from that binary
+    // subtraction, only the accumulator gets the BCD result. the 65C02 also
+    // sets N and Z from the BCD result, and adjusts invalid digits differently
+    fn sbc(&mut self, value: Byte) {
+        if !self.decimal_mode() {
+            self.adc_binary(!value);
+            return;
+        }
+
+        let cmos = self.variant.is_cmos();
+        let result = if cmos {
+            self.sbc_decimal_result_cmos(value)
+        } else {
+            self.sbc_decimal_result(value)
+        };
+        self.adc_binary(!value);
+        self.accumulator = result;
+        if cmos {
+            self.set_zero_negative(result);
+        }
+    }
+
+    fn adc_binary(&mut self, value: Byte) {
         let carry_in = if self.flags.carry() { 1 } else { 0 };
         let sum = self.accumulator as u16 + value as u16 + carry_in;
         let result = sum as u8;
@@ -307,6 +1455,68 @@ impl CPU {
         self.accumulator = result;
     }
 
+    // NMOS decimal adc, following Bruce Clark's "Decimal Mode" tutorial
+    // (http://www.6502.org/tutorials/decimal_mode.html, appendix A). Z comes
+    // from the binary sum, N and V from the sum before the high nibble is
+    // adjusted, and invalid BCD digits go through the same adjustments
+    fn adc_decimal(&mut self, value: Byte) {
+        let carry_in = if self.flags.carry() { 1 } else { 0 };
+        let binary_result = (self.accumulator as u16 + value as u16 + carry_in) as Byte;
+
+        let mut low = (self.accumulator & 0x0F) as u16 + (value & 0x0F) as u16 + carry_in;
+        if low >= 0x0A {
+            low = ((low + 0x06) & 0x0F) + 0x10;
+        }
+
+        let signed_sum =
+            (self.accumulator & 0xF0) as i8 as i16 + (value & 0xF0) as i8 as i16 + low as i16;
+
+        let mut sum = (self.accumulator & 0xF0) as u16 + (value & 0xF0) as u16 + low;
+        if sum >= 0xA0 {
+            sum += 0x60;
+        }
+
+        self.flags.set_carry(sum >= 0x100);
+        self.flags.set_zero(binary_result == 0);
+        self.flags.set_negative(signed_sum & 0x80 != 0);
+        self.flags.set_overflow(!(-128..=127).contains(&signed_sum));
+
+        self.accumulator = sum as Byte;
+    }
+
+    fn sbc_decimal_result(&self, value: Byte) -> Byte {
+        let carry_in = if self.flags.carry() { 1 } else { 0 };
+
+        let mut low = (self.accumulator & 0x0F) as i16 - (value & 0x0F) as i16 + carry_in - 1;
+        if low < 0 {
+            low = ((low - 0x06) & 0x0F) - 0x10;
+        }
+
+        let mut difference = (self.accumulator & 0xF0) as i16 - (value & 0xF0) as i16 + low;
+        if difference < 0 {
+            difference -= 0x60;
+        }
+
+        difference as Byte
+    }
+
+    // sequence 4 from the same tutorial: the high nibble is fixed first on
+    // the full difference, then the low nibble borrow is taken off it
+    fn sbc_decimal_result_cmos(&self, value: Byte) -> Byte {
+        let carry_in = if self.flags.carry() { 1 } else { 0 };
+
+        let low = (self.accumulator & 0x0F) as i16 - (value & 0x0F) as i16 + carry_in - 1;
+        let mut difference = self.accumulator as i16 - value as i16 + carry_in - 1;
+        if difference < 0 {
+            difference -= 0x60;
+        }
+        if low < 0 {
+            difference -= 0x06;
+        }
+
+        difference as Byte
+    }
+
     //getters for flags for testing
     pub fn get_carry_flag(&self) -> bool {
         self.flags.carry()
@@ -354,7 +1564,7 @@ impl CPU {
     }
 
     //getters for CPU registers for testing
-    pub fn get_stack_register(&self) -> Word {
+    pub fn get_stack_register(&self) -> Byte {
         self.stack_register
     }
     pub fn get_program_counter(&self) -> Word {
@@ -372,6 +1582,15 @@ impl CPU {
     pub fn get_flags(&self) -> CpuFlags {
         self.flags
     }
+    pub fn get_total_cycles(&self) -> u64 {
+        self.total_cycles
+    }
+    pub fn get_halt_state(&self) -> Option<HaltState> {
+        self.halt_state
+    }
+    pub fn get_variant(&self) -> Variant {
+        self.variant
+    }
 
     //setters for CPU registers for testing
     pub fn set_accumulator(&mut self, value: Byte) {
@@ -388,17 +1607,28 @@ impl CPU {
     pub fn set_program_counter(&mut self, value: Word) {
         self.program_counter = value;
     }
-    pub fn set_stack_register(&mut self, value: Word) {
+    pub fn set_stack_register(&mut self, value: Byte) {
         self.stack_register = value;
     }
 }
 
+// indexed address and whether the index carried into the high byte
+fn add_index(base_addr: Word, index: Byte) -> (Word, bool) {
+    let addr = base_addr.wrapping_add(index as Word);
+    (addr, (base_addr & 0xFF00) != (addr & 0xFF00))
+}
+
+// src/main.rs is also the library root, where main is never called
+#[allow(dead_code)]
 fn main() {
     let mut mem = Mem::new();
     let mut cpu = CPU::default();
-    let cycles = 2;
+    let cycles = 7 + 2;
     cpu.reset();
-    mem[0xfffc] = Opcode::LdaIm as u8;
-    mem[0xfffd] = 0x42;
-    cpu.execute(&mut mem, cycles);
+    mem.write_word(0xfffc, 0x8000);
+    mem[0x8000] = Opcode::LdaIm as u8;
+    mem[0x8001] = 0x42;
+    if let Err(error) = cpu.execute(&mut mem, cycles) {
+        eprintln!("{error}");
+    }
 }
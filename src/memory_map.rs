@@ -0,0 +1,288 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use crate::{Bus, Byte, Mem, Word};
+
+// routes CPU addresses to RAM, ROM and device handlers. every handler sees
+// addresses relative to the start of its region, already folded down to its
+// mirror size, so a device with 16 registers always gets 0..=15.
+// reads from unmapped addresses return 0 and writes to them are dropped
+pub struct MemoryMap {
+    regions: Vec<Region>,
+}
+
+struct Region {
+    range: RangeInclusive<Word>,
+    mirror_size: usize,
+    handler: Box<dyn Bus>,
+}
+
+impl Region {
+    fn offset(&self, addr: Word) -> Word {
+        ((addr - self.range.start()) as usize % self.mirror_size) as Word
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MemoryMapError {
+    // start of the range is after its end
+    EmptyRange(RangeInclusive<Word>),
+    // the mirrored block is zero bytes, bigger than the range, or does not
+    // repeat a whole number of times inside it
+    InvalidMirror {
+        range: RangeInclusive<Word>,
+        size: usize,
+    },
+    Overlap {
+        first: RangeInclusive<Word>,
+        second: RangeInclusive<Word>,
+    },
+}
+
+impl fmt::Display for MemoryMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MemoryMapError::EmptyRange(range) => {
+                write!(f, "empty range ${:04X}-${:04X}", range.start(), range.end())
+            }
+            MemoryMapError::InvalidMirror { range, size } => write!(
+                f,
+                "a {size} byte block cannot be mirrored across ${:04X}-${:04X}",
+                range.start(),
+                range.end()
+            ),
+            MemoryMapError::Overlap { first, second } => write!(
+                f,
+                "${:04X}-${:04X} overlaps ${:04X}-${:04X}",
+                second.start(),
+                second.end(),
+                first.start(),
+                first.end()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MemoryMapError {}
+
+impl MemoryMap {
+    pub fn builder() -> MemoryMapBuilder {
+        MemoryMapBuilder::new()
+    }
+
+    fn region_mut(&mut self, addr: Word) -> Option<&mut Region> {
+        self.regions
+            .iter_mut()
+            .find(|region| region.range.contains(&addr))
+    }
+
+    fn region(&self, addr: Word) -> Option<&Region> {
+        self.regions
+            .iter()
+            .find(|region| region.range.contains(&addr))
+    }
+}
+
+impl Bus for MemoryMap {
+    fn read(&mut self, addr: Word) -> Byte {
+        match self.region_mut(addr) {
+            Some(region) => {
+                let offset = region.offset(addr);
+                region.handler.read(offset)
+            }
+            None => 0,
+        }
+    }
+
+    fn write(&mut self, addr: Word, value: Byte) {
+        if let Some(region) = self.region_mut(addr) {
+            let offset = region.offset(addr);
+            region.handler.write(offset, value);
+        }
+    }
+
+    fn peek(&self, addr: Word) -> Byte {
+        match self.region(addr) {
+            Some(region) => region.handler.peek(region.offset(addr)),
+            None => 0,
+        }
+    }
+
+    fn sync(&mut self, total_cycles: u64) {
+        for region in &mut self.regions {
+            region.handler.sync(total_cycles);
+        }
+    }
+}
+
+// the whole address space as one block of RAM, same as using `Mem` directly
+impl From<Mem> for MemoryMap {
+    fn from(memory: Mem) -> Self {
+        MemoryMap {
+            regions: vec![Region {
+                range: 0x0000..=0xFFFF,
+                mirror_size: 0x10000,
+                handler: Box::new(memory),
+            }],
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryMapBuilder {
+    regions: Vec<PendingRegion>,
+}
+
+// a region as the builder holds it. RAM is only allocated by `build`, once
+// its mirror size has been checked
+struct PendingRegion {
+    range: RangeInclusive<Word>,
+    mirror_size: usize,
+    handler: PendingHandler,
+}
+
+enum PendingHandler {
+    Ram,
+    Bus(Box<dyn Bus>),
+}
+
+impl MemoryMapBuilder {
+    pub fn new() -> Self {
+        MemoryMapBuilder {
+            regions: Vec::new(),
+        }
+    }
+
+    pub fn ram(self, range: RangeInclusive<Word>) -> Self {
+        let size = range_len(&range);
+        self.ram_mirrored(range, size)
+    }
+
+    // `size` bytes of RAM repeated across the whole range
+    pub fn ram_mirrored(self, range: RangeInclusive<Word>, size: usize) -> Self {
+        self.region(range, size, PendingHandler::Ram)
+    }
+
+    // read-only region, writes are ignored. an image shorter than the range
+    // is mirrored to fill it
+    pub fn rom(self, range: RangeInclusive<Word>, data: Vec<Byte>) -> Self {
+        let size = data.len();
+        self.region(range, size, PendingHandler::Bus(Box::new(Rom { data })))
+    }
+
+    pub fn device(self, range: RangeInclusive<Word>, handler: impl Bus + 'static) -> Self {
+        let size = range_len(&range);
+        self.device_mirrored(range, size, handler)
+    }
+
+    // a device with `size` registers repeated across the whole range
+    pub fn device_mirrored(
+        self,
+        range: RangeInclusive<Word>,
+        size: usize,
+        handler: impl Bus + 'static,
+    ) -> Self {
+        self.region(range, size, PendingHandler::Bus(Box::new(handler)))
+    }
+
+    pub fn build(self) -> Result<MemoryMap, MemoryMapError> {
+        for (index, region) in self.regions.iter().enumerate() {
+            let range = &region.range;
+            if range.is_empty() {
+                return Err(MemoryMapError::EmptyRange(range.clone()));
+            }
+
+            let size = region.mirror_size;
+            if size == 0 || size > range_len(range) || !range_len(range).is_multiple_of(size) {
+                return Err(MemoryMapError::InvalidMirror {
+                    range: range.clone(),
+                    size,
+                });
+            }
+
+            for earlier in &self.regions[..index] {
+                let earlier = &earlier.range;
+                if range.start() <= earlier.end() && earlier.start() <= range.end() {
+                    return Err(MemoryMapError::Overlap {
+                        first: earlier.clone(),
+                        second: range.clone(),
+                    });
+                }
+            }
+        }
+
+        let regions = self
+            .regions
+            .into_iter()
+            .map(|region| Region {
+                handler: match region.handler {
+                    PendingHandler::Ram => Box::new(Ram::new(region.mirror_size)),
+                    PendingHandler::Bus(handler) => handler,
+                },
+                range: region.range,
+                mirror_size: region.mirror_size,
+            })
+            .collect();
+
+        Ok(MemoryMap { regions })
+    }
+
+    fn region(mut self, range: RangeInclusive<Word>, size: usize, handler: PendingHandler) -> Self {
+        self.regions.push(PendingRegion {
+            range,
+            mirror_size: size,
+            handler,
+        });
+        self
+    }
+}
+
+fn range_len(range: &RangeInclusive<Word>) -> usize {
+    if range.is_empty() {
+        0
+    } else {
+        (range.end() - range.start()) as usize + 1
+    }
+}
+
+struct Ram {
+    data: Vec<Byte>,
+}
+
+impl Ram {
+    fn new(size: usize) -> Self {
+        Ram {
+            data: vec![0; size],
+        }
+    }
+}
+
+impl Bus for Ram {
+    fn read(&mut self, addr: Word) -> Byte {
+        self.data[addr as usize]
+    }
+
+    fn write(&mut self, addr: Word, value: Byte) {
+        self.data[addr as usize] = value;
+    }
+
+    fn peek(&self, addr: Word) -> Byte {
+        self.data[addr as usize]
+    }
+}
+
+struct Rom {
+    data: Vec<Byte>,
+}
+
+impl Bus for Rom {
+    fn read(&mut self, addr: Word) -> Byte {
+        self.data[addr as usize]
+    }
+
+    fn write(&mut self, _addr: Word, _value: Byte) {}
+
+    fn peek(&self, addr: Word) -> Byte {
+        self.data[addr as usize]
+    }
+}
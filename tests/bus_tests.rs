@@ -0,0 +1,127 @@
+use cpu6052::*;
+
+// flat RAM that also records every write, to check what the CPU puts on the
+// bus and in which order
+struct RecordingBus {
+    memory: Mem,
+    writes: Vec<(u16, u8)>,
+}
+
+impl RecordingBus {
+    fn new() -> Self {
+        RecordingBus {
+            memory: Mem::new(),
+            writes: Vec::new(),
+        }
+    }
+}
+
+impl Bus for RecordingBus {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.memory.read(addr)
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        self.writes.push((addr, value));
+        self.memory.write(addr, value);
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        self.memory.peek(addr)
+    }
+}
+
+#[test]
+fn test_mem_bus_read_write() {
+    let mut memory = Mem::new();
+
+    memory.write(0x1234, 0x42);
+
+    assert_eq!(memory.read(0x1234), 0x42);
+    assert_eq!(memory.peek(0x1234), 0x42);
+    assert_eq!(memory[0x1234], 0x42, "Indexing should see bus writes");
+}
+
+#[test]
+fn test_mem_word_wraps_at_ffff() {
+    let mut memory = Mem::new();
+
+    memory.write_word(0xFFFF, 0x1234);
+
+    assert_eq!(memory[0xFFFF], 0x34);
+    assert_eq!(memory[0x0000], 0x12, "The high byte should wrap to $0000");
+    assert_eq!(memory.read_word(0xFFFF), 0x1234);
+}
+
+#[test]
+fn test_mem_zero_page_word_wraps_at_ff() {
+    let mut memory = Mem::new();
+    memory[0x00FF] = 0x34;
+    memory[0x0000] = 0x12;
+    memory[0x0100] = 0x56;
+
+    assert_eq!(
+        memory.read_word_zero_page(0xFF),
+        0x1234,
+        "The high byte should come from $00, not $0100"
+    );
+    assert_eq!(memory.read_word(0x00FF), 0x5634);
+}
+
+#[test]
+fn test_cpu_runs_on_custom_bus() {
+    let mut bus = RecordingBus::new();
+    let mut cpu = CPU::default();
+
+    // LDA #$42, STA $2000
+    bus.memory.write_word(0xFFFC, 0x8000);
+    bus.memory[0x8000] = Opcode::LdaIm as u8;
+    bus.memory[0x8001] = 0x42;
+    bus.memory[0x8002] = Opcode::StaAbs as u8;
+    bus.memory[0x8003] = 0x00;
+    bus.memory[0x8004] = 0x20;
+
+    cpu.reset();
+    cpu.execute(&mut bus, 7 + 2 + 4).unwrap();
+
+    assert_eq!(bus.peek(0x2000), 0x42);
+    assert_eq!(bus.writes, vec![(0x2000, 0x42)]);
+}
+
+#[test]
+fn test_read_modify_write_writes_twice() {
+    let mut bus = RecordingBus::new();
+    let mut cpu = CPU::default();
+
+    // INC $D019 writes the old value back before the incremented one
+    bus.memory[0xFFFC] = Opcode::IncAbs as u8;
+    bus.memory[0xFFFD] = 0x19;
+    bus.memory[0xFFFE] = 0xD0;
+    bus.memory[0xD019] = 0x41;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.execute(&mut bus, 6).unwrap();
+
+    assert_eq!(bus.writes, vec![(0xD019, 0x41), (0xD019, 0x42)]);
+}
+
+#[test]
+fn test_cmos_bit_instructions_write_once() {
+    let mut bus = RecordingBus::new();
+    let mut cpu = CPU::new(Variant::Wdc65C02);
+
+    // SMB0 $19 goes through the same read-modify-write path as INC, so the
+    // 65C02 only writes the result
+    bus.memory[0xFFFC] = Variant::Wdc65C02
+        .encode(Mnemonic::Smb0, AddressingMode::ZeroPage)
+        .unwrap();
+    bus.memory[0xFFFD] = 0x19;
+    bus.memory[0x0019] = 0x40;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.execute(&mut bus, 5).unwrap();
+
+    assert_eq!(bus.writes, vec![(0x0019, 0x41)]);
+}
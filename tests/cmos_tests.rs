@@ -0,0 +1,431 @@
+mod common;
+
+use common::{opcode, setup_variant};
+use cpu6052::*;
+
+// loads `program` at $0200 on a 65C02
+fn setup(program: &[u8]) -> (Mem, CPU) {
+    setup_variant(Variant::Cmos65C02, program)
+}
+
+#[test]
+fn test_default_cpu_is_nmos() {
+    assert_eq!(CPU::default().get_variant(), Variant::Nmos6502);
+    assert_eq!(
+        CPU::new(Variant::Cmos65C02).get_variant(),
+        Variant::Cmos65C02
+    );
+}
+
+#[test]
+fn test_cmos_reserved_opcodes_are_nops() {
+    let table = Variant::Cmos65C02.decode_table();
+    assert!(table.iter().all(|entry| entry.is_some()));
+
+    for (opcode, instruction) in table.iter().flatten().enumerate() {
+        if instruction.undocumented {
+            assert_eq!(instruction.mnemonic, Mnemonic::Nop, "${opcode:02X}");
+        }
+        if instruction.cycles == 1 {
+            assert!(
+                matches!(opcode & 0x0F, 0x03 | 0x07 | 0x0B | 0x0F),
+                "${opcode:02X} should not be a single-cycle NOP"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_cmos_reserved_nop_timing() {
+    let (mut memory, mut cpu) = setup(&[0x03, 0x5C, 0x00, 0x00, 0xEB]);
+
+    assert_eq!(cpu.step(&mut memory).unwrap().cycles, 1);
+    assert_eq!(cpu.step(&mut memory).unwrap().cycles, 8);
+    assert_eq!(cpu.get_program_counter(), 0x0204);
+
+    // no longer an SBC alias
+    cpu.set_accumulator(0x42);
+    cpu.step(&mut memory).unwrap();
+    assert_eq!(
+        cpu.get_accumulator(),
+        0x42,
+        "Accumulator should be untouched"
+    );
+}
+
+#[test]
+fn test_bra_always_branches() {
+    let (mut memory, mut cpu) = setup(&[
+        opcode(Variant::Cmos65C02, Mnemonic::Bra, AddressingMode::Relative),
+        0x10,
+    ]);
+
+    let result = cpu.step(&mut memory).unwrap();
+
+    assert_eq!(cpu.get_program_counter(), 0x0212);
+    assert_eq!(result.cycles, 3);
+}
+
+#[test]
+fn test_phx_ply() {
+    let (mut memory, mut cpu) = setup(&[
+        opcode(Variant::Cmos65C02, Mnemonic::Phx, AddressingMode::Implied),
+        opcode(Variant::Cmos65C02, Mnemonic::Ply, AddressingMode::Implied),
+    ]);
+    cpu.set_index_register_x(0x80);
+
+    cpu.execute(&mut memory, 3 + 4).unwrap();
+
+    assert_eq!(cpu.get_index_register_y(), 0x80, "Y should be 0x80");
+    assert!(cpu.get_negative_flag(), "Negative flag should be set");
+    assert_eq!(cpu.get_stack_register(), 0xFF);
+}
+
+#[test]
+fn test_stz_abs_x() {
+    let (mut memory, mut cpu) = setup(&[
+        opcode(Variant::Cmos65C02, Mnemonic::Stz, AddressingMode::AbsoluteX),
+        0x00,
+        0x20,
+    ]);
+    memory[0x2005] = 0x42;
+    cpu.set_index_register_x(0x05);
+
+    let result = cpu.step(&mut memory).unwrap();
+
+    assert_eq!(memory[0x2005], 0x00, "Memory at 0x2005 should be cleared");
+    assert_eq!(result.cycles, 5);
+}
+
+#[test]
+fn test_tsb_and_trb() {
+    let (mut memory, mut cpu) = setup(&[
+        opcode(Variant::Cmos65C02, Mnemonic::Tsb, AddressingMode::ZeroPage),
+        0x10,
+        opcode(Variant::Cmos65C02, Mnemonic::Trb, AddressingMode::ZeroPage),
+        0x10,
+    ]);
+    memory[0x0010] = 0x0C;
+    cpu.set_accumulator(0x03);
+
+    cpu.step(&mut memory).unwrap();
+    assert_eq!(memory[0x0010], 0x0F, "TSB should set the bits of A");
+    assert!(cpu.get_zero_flag(), "Zero flag should come from A & M");
+
+    cpu.step(&mut memory).unwrap();
+    assert_eq!(memory[0x0010], 0x0C, "TRB should clear the bits of A");
+    assert!(!cpu.get_zero_flag(), "Zero flag should come from A & M");
+}
+
+#[test]
+fn test_lda_zero_page_indirect() {
+    let (mut memory, mut cpu) = setup(&[
+        opcode(
+            Variant::Cmos65C02,
+            Mnemonic::Lda,
+            AddressingMode::ZeroPageIndirect,
+        ),
+        0x40,
+    ]);
+    memory[0x0040] = 0x00;
+    memory[0x0041] = 0x30;
+    memory[0x3000] = 0x42;
+
+    let result = cpu.step(&mut memory).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x42, "Accumulator should be 0x42");
+    assert_eq!(result.cycles, 5);
+}
+
+#[test]
+fn test_inc_a_and_dec_a() {
+    let (mut memory, mut cpu) = setup(&[
+        opcode(
+            Variant::Cmos65C02,
+            Mnemonic::Inc,
+            AddressingMode::Accumulator,
+        ),
+        opcode(
+            Variant::Cmos65C02,
+            Mnemonic::Dec,
+            AddressingMode::Accumulator,
+        ),
+        opcode(
+            Variant::Cmos65C02,
+            Mnemonic::Dec,
+            AddressingMode::Accumulator,
+        ),
+    ]);
+    cpu.set_accumulator(0xFF);
+
+    cpu.step(&mut memory).unwrap();
+    assert_eq!(
+        cpu.get_accumulator(),
+        0x00,
+        "Accumulator should wrap to 0x00"
+    );
+    assert!(cpu.get_zero_flag(), "Zero flag should be set");
+
+    cpu.execute(&mut memory, 4).unwrap();
+    assert_eq!(cpu.get_accumulator(), 0xFE, "Accumulator should be 0xFE");
+    assert!(cpu.get_negative_flag(), "Negative flag should be set");
+}
+
+#[test]
+fn test_bit_immediate_only_sets_zero() {
+    let (mut memory, mut cpu) = setup(&[
+        opcode(Variant::Cmos65C02, Mnemonic::Bit, AddressingMode::Immediate),
+        0xC0,
+    ]);
+    cpu.set_accumulator(0x01);
+
+    cpu.step(&mut memory).unwrap();
+
+    assert!(cpu.get_zero_flag(), "Zero flag should be set");
+    assert!(
+        !cpu.get_negative_flag(),
+        "Negative flag should be untouched"
+    );
+    assert!(
+        !cpu.get_overflow_flag(),
+        "Overflow flag should be untouched"
+    );
+}
+
+#[test]
+fn test_jmp_indirect_page_bug_is_fixed() {
+    let (mut memory, mut cpu) = setup(&[Opcode::JmpInd as u8, 0xFF, 0x10]);
+    memory[0x10FF] = 0x34;
+    memory[0x1100] = 0x12;
+    memory[0x1000] = 0x56;
+
+    let result = cpu.step(&mut memory).unwrap();
+
+    assert_eq!(cpu.get_program_counter(), 0x1234);
+    assert_eq!(result.cycles, 6);
+}
+
+#[test]
+fn test_jmp_absolute_indexed_indirect() {
+    let (mut memory, mut cpu) = setup(&[
+        opcode(
+            Variant::Cmos65C02,
+            Mnemonic::Jmp,
+            AddressingMode::AbsoluteIndexedIndirect,
+        ),
+        0x00,
+        0x30,
+    ]);
+    memory[0x3004] = 0x34;
+    memory[0x3005] = 0x12;
+    cpu.set_index_register_x(0x04);
+
+    cpu.step(&mut memory).unwrap();
+
+    assert_eq!(cpu.get_program_counter(), 0x1234);
+}
+
+#[test]
+fn test_adc_decimal_sets_valid_flags_and_takes_a_cycle() {
+    let (mut memory, mut cpu) = setup(&[Opcode::AdcIm as u8, 0x01]);
+    cpu.set_decimal_flag(true);
+    cpu.set_accumulator(0x99);
+
+    let result = cpu.step(&mut memory).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x00, "Accumulator should be 0x00");
+    assert!(cpu.get_carry_flag(), "Carry flag should be set");
+    assert!(cpu.get_zero_flag(), "Zero flag should match the BCD result");
+    assert!(!cpu.get_negative_flag(), "Negative flag should be cleared");
+    assert_eq!(result.cycles, 3);
+}
+
+#[test]
+fn test_sbc_decimal_sets_valid_flags() {
+    let (mut memory, mut cpu) = setup(&[Opcode::SbcIm as u8, 0x01]);
+    cpu.set_decimal_flag(true);
+    cpu.set_carry_flag(true);
+    cpu.set_accumulator(0x00);
+
+    let result = cpu.step(&mut memory).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x99, "Accumulator should be 0x99");
+    assert!(
+        !cpu.get_carry_flag(),
+        "Carry flag should be cleared, borrow"
+    );
+    assert!(
+        cpu.get_negative_flag(),
+        "Negative flag should match the BCD result"
+    );
+    assert_eq!(result.cycles, 3);
+}
+
+#[test]
+fn test_brk_clears_decimal() {
+    let (mut memory, mut cpu) = setup(&[Opcode::Brk as u8]);
+    memory.write_word(0xFFFE, 0x8000);
+    cpu.set_decimal_flag(true);
+
+    cpu.step(&mut memory).unwrap();
+
+    assert_eq!(cpu.get_program_counter(), 0x8000);
+    assert!(!cpu.get_decimal_flag(), "Decimal flag should be cleared");
+    assert_eq!(
+        memory[0x01FD] & 0b00001000,
+        0b00001000,
+        "Pushed D should be set"
+    );
+}
+
+#[test]
+fn test_wdc_table_adds_bit_instructions() {
+    let rmb0 = Variant::Wdc65C02.decode(0x07).unwrap();
+    assert_eq!(rmb0.mnemonic, Mnemonic::Rmb0);
+    assert_eq!(rmb0.mnemonic.bit(), Some(0));
+
+    let bbs7 = Variant::Wdc65C02.decode(0xFF).unwrap();
+    assert_eq!(bbs7.mnemonic, Mnemonic::Bbs7);
+    assert_eq!(bbs7.mode, AddressingMode::ZeroPageRelative);
+    assert_eq!(bbs7.size(), 3);
+
+    assert_eq!(
+        Variant::Wdc65C02.decode(0xCB).unwrap().mnemonic,
+        Mnemonic::Wai
+    );
+    assert_eq!(
+        Variant::Cmos65C02.decode(0xCB).unwrap().mnemonic,
+        Mnemonic::Nop
+    );
+    assert_eq!(Mnemonic::Smb3.to_string(), "SMB3");
+}
+
+#[test]
+fn test_rmb_and_smb() {
+    let (mut memory, mut cpu) = setup_variant(
+        Variant::Wdc65C02,
+        &[
+            opcode(Variant::Wdc65C02, Mnemonic::Smb7, AddressingMode::ZeroPage),
+            0x10,
+            opcode(Variant::Wdc65C02, Mnemonic::Rmb0, AddressingMode::ZeroPage),
+            0x10,
+        ],
+    );
+    memory[0x0010] = 0x01;
+
+    let result = cpu.step(&mut memory).unwrap();
+    assert_eq!(memory[0x0010], 0x81, "SMB7 should set bit 7");
+    assert_eq!(result.cycles, 5);
+
+    cpu.step(&mut memory).unwrap();
+    assert_eq!(memory[0x0010], 0x80, "RMB0 should clear bit 0");
+}
+
+#[test]
+fn test_bbr_and_bbs() {
+    let (mut memory, mut cpu) = setup_variant(
+        Variant::Wdc65C02,
+        &[
+            opcode(
+                Variant::Wdc65C02,
+                Mnemonic::Bbs2,
+                AddressingMode::ZeroPageRelative,
+            ),
+            0x10,
+            0x10,
+            opcode(
+                Variant::Wdc65C02,
+                Mnemonic::Bbr2,
+                AddressingMode::ZeroPageRelative,
+            ),
+            0x10,
+            0x10,
+        ],
+    );
+    memory[0x0010] = 0x03;
+
+    let result = cpu.step(&mut memory).unwrap();
+    assert_eq!(
+        cpu.get_program_counter(),
+        0x0203,
+        "BBS2 should fall through"
+    );
+    assert_eq!(result.cycles, 5);
+
+    let result = cpu.step(&mut memory).unwrap();
+    assert_eq!(cpu.get_program_counter(), 0x0216, "BBR2 should branch");
+    assert_eq!(result.cycles, 6);
+}
+
+#[test]
+fn test_wai_sleeps_until_irq() {
+    let (mut memory, mut cpu) = setup_variant(
+        Variant::Wdc65C02,
+        &[opcode(
+            Variant::Wdc65C02,
+            Mnemonic::Wai,
+            AddressingMode::Implied,
+        )],
+    );
+    memory.write_word(0xFFFE, 0x8000);
+
+    let result = cpu.step(&mut memory).unwrap();
+    assert_eq!(result.halted, Some(HaltState::Waiting));
+    assert_eq!(cpu.step(&mut memory).unwrap().cycles, 1);
+
+    // nothing can wake the CPU inside a slice, so it skips to the end
+    let before = cpu.get_total_cycles();
+    assert_eq!(cpu.execute(&mut memory, 1000), Ok(0));
+    assert_eq!(cpu.get_total_cycles(), before + 1000);
+
+    cpu.set_irq(true);
+    let result = cpu.step(&mut memory).unwrap();
+
+    assert_eq!(result.interrupt, Some(Interrupt::Irq));
+    assert_eq!(cpu.get_halt_state(), None);
+    assert_eq!(cpu.get_program_counter(), 0x8000);
+    assert_eq!(memory[0x01FE], 0x01, "Return address should follow the WAI");
+}
+
+#[test]
+fn test_wai_with_interrupts_disabled_resumes_without_the_irq() {
+    let (mut memory, mut cpu) = setup_variant(
+        Variant::Wdc65C02,
+        &[
+            opcode(Variant::Wdc65C02, Mnemonic::Wai, AddressingMode::Implied),
+            Opcode::Inx as u8,
+        ],
+    );
+    cpu.set_interrupt_disable_flag(true);
+
+    cpu.step(&mut memory).unwrap();
+    cpu.set_irq(true);
+    let result = cpu.step(&mut memory).unwrap();
+
+    assert_eq!(result.interrupt, None);
+    assert_eq!(result.opcode, Some(Opcode::Inx as u8));
+    assert_eq!(cpu.get_index_register_x(), 0x01, "X should be 0x01");
+}
+
+#[test]
+fn test_stp_stops_until_reset() {
+    let (mut memory, mut cpu) = setup_variant(
+        Variant::Wdc65C02,
+        &[opcode(
+            Variant::Wdc65C02,
+            Mnemonic::Stp,
+            AddressingMode::Implied,
+        )],
+    );
+
+    cpu.step(&mut memory).unwrap();
+    cpu.set_irq(true);
+    cpu.set_nmi(true);
+    let result = cpu.step(&mut memory).unwrap();
+
+    assert_eq!(result.halted, Some(HaltState::Stopped));
+    assert_eq!(result.interrupt, None);
+
+    cpu.reset();
+    assert_eq!(cpu.get_halt_state(), None);
+}
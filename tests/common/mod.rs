@@ -0,0 +1,23 @@
+use cpu6052::*;
+
+// the opcode `variant` uses for an instruction
+pub fn opcode(variant: Variant, mnemonic: Mnemonic, mode: AddressingMode) -> u8 {
+    variant
+        .encode(mnemonic, mode)
+        .expect("opcode should be in the decode table")
+}
+
+// loads `program` at $0200 and points PC at it, skipping the reset sequence
+pub fn setup_variant(variant: Variant, program: &[u8]) -> (Mem, CPU) {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::new(variant);
+
+    for (offset, byte) in program.iter().enumerate() {
+        memory[0x0200 + offset] = *byte;
+    }
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_program_counter(0x0200);
+    (memory, cpu)
+}
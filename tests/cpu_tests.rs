@@ -3,6 +3,7 @@ use cpu6052::*;
 #[test]
 fn test_cpu_reset_flags() {
     let mut cpu = CPU::default();
+    cpu.set_legacy_reset(true);
     cpu.reset();
 
     assert!(!cpu.get_carry_flag());
@@ -24,8 +25,9 @@ fn test_lda_im() {
     memory[0xFFFD] = 0x42; // Value to load into the accumulator
 
     // Execute the instruction
+    cpu.set_legacy_reset(true);
     cpu.reset();
-    cpu.execute(&mut memory, 2);
+    cpu.execute(&mut memory, 2).unwrap();
 
     // Assert that the accumulator is updated correctly
     assert_eq!(cpu.get_accumulator(), 0x42);
@@ -48,8 +50,9 @@ fn test_lda_zp() {
     memory[0x0010] = 0x42; // Value to load into the accumulator
 
     // Execute the instruction
+    cpu.set_legacy_reset(true);
     cpu.reset();
-    cpu.execute(&mut memory, 3);
+    cpu.execute(&mut memory, 3).unwrap();
 
     // Assert that the accumulator is updated correctly
     assert_eq!(cpu.get_accumulator(), 0x42);
@@ -72,9 +75,10 @@ fn test_lda_zpx() {
     memory[0x0015] = 0x42; // Value to load into the accumulator (0x10 + X = 0x15)
 
     // Execute the instruction
+    cpu.set_legacy_reset(true);
     cpu.reset();
     cpu.set_index_register_x(0x05);
-    cpu.execute(&mut memory, 4);
+    cpu.execute(&mut memory, 4).unwrap();
 
     // Assert that the accumulator is updated correctly
     assert_eq!(cpu.get_accumulator(), 0x42, "Accumulator should be 0x42");
@@ -97,8 +101,9 @@ fn test_jsr() {
     memory[0xFFFE] = 0x20; // High byte of subroutine address
 
     // Execute the instruction
+    cpu.set_legacy_reset(true);
     cpu.reset();
-    cpu.execute(&mut memory, 6);
+    cpu.execute(&mut memory, 6).unwrap();
 
     // Assert that the program counter is set to the subroutine address
     assert_eq!(
@@ -108,17 +113,17 @@ fn test_jsr() {
     );
 
     // Assert that the return address is pushed onto the stack
-    let return_addr_low = memory[cpu.get_stack_register() as usize + 1];
-    let return_addr_high = memory[cpu.get_stack_register() as usize + 2];
+    let return_addr_low = memory[0x0100 + cpu.get_stack_register() as usize + 1];
+    let return_addr_high = memory[0x0100 + cpu.get_stack_register() as usize + 2];
     let return_addr = ((return_addr_high as u16) << 8) | return_addr_low as u16;
 
-    assert_eq!(return_addr, 0xFFFD, "Return address should be 0xFFFD");
+    assert_eq!(return_addr, 0xFFFE, "Return address should be 0xFFFE");
 
     // Assert that the stack pointer is decremented correctly
     assert_eq!(
         cpu.get_stack_register(),
-        0x00FD,
-        "Stack pointer should be 0x00FD"
+        0xFD,
+        "Stack pointer should be 0xFD"
     );
 }
 
@@ -132,12 +137,13 @@ fn test_adc_im() {
     memory[0xFFFD] = 0x10; // Value to add to the accumulator
 
     // Initialize the accumulator and carry flag
+    cpu.set_legacy_reset(true);
     cpu.reset();
     cpu.set_accumulator(0x20); // Set accumulator to 0x20
     cpu.set_carry_flag(false); // Ensure carry flag is cleared
 
     // Execute the instruction
-    cpu.execute(&mut memory, 2);
+    cpu.execute(&mut memory, 2).unwrap();
 
     // Assert that the accumulator is updated correctly
     assert_eq!(cpu.get_accumulator(), 0x30, "Accumulator should be 0x30");
@@ -163,12 +169,13 @@ fn test_adc_zp() {
     memory[0x0010] = 0x15; // Value to add to the accumulator
 
     // Initialize the accumulator and carry flag
+    cpu.set_legacy_reset(true);
     cpu.reset();
     cpu.set_accumulator(0x20); // Set accumulator to 0x20
     cpu.set_carry_flag(false); // Ensure carry flag is cleared
 
     // Execute the instruction
-    cpu.execute(&mut memory, 3);
+    cpu.execute(&mut memory, 3).unwrap();
 
     // Assert that the accumulator is updated correctly
     assert_eq!(cpu.get_accumulator(), 0x35, "Accumulator should be 0x35");
@@ -194,13 +201,14 @@ fn test_adc_zpx() {
     memory[0x0015] = 0x15; // Value to add to the accumulator (0x10 + X = 0x15)
 
     // Initialize the accumulator, index register X, and carry flag
+    cpu.set_legacy_reset(true);
     cpu.reset();
     cpu.set_accumulator(0x20); // Set accumulator to 0x20
     cpu.set_index_register_x(0x05); // Set X register to 0x05
     cpu.set_carry_flag(false); // Ensure carry flag is cleared
 
     // Execute the instruction
-    cpu.execute(&mut memory, 4);
+    cpu.execute(&mut memory, 4).unwrap();
 
     // Assert that the accumulator is updated correctly
     assert_eq!(cpu.get_accumulator(), 0x35, "Accumulator should be 0x35");
@@ -227,12 +235,13 @@ fn test_adc_abs() {
     memory[0x2000] = 0x15; // Value to add to the accumulator
 
     // Initialize the accumulator and carry flag
+    cpu.set_legacy_reset(true);
     cpu.reset();
     cpu.set_accumulator(0x20); // Set accumulator to 0x20
     cpu.set_carry_flag(false); // Ensure carry flag is cleared
 
     // Execute the instruction
-    cpu.execute(&mut memory, 4);
+    cpu.execute(&mut memory, 4).unwrap();
 
     // Assert that the accumulator is updated correctly
     assert_eq!(cpu.get_accumulator(), 0x35, "Accumulator should be 0x35");
@@ -246,3 +255,1956 @@ fn test_adc_abs() {
     // Assert that the negative flag is cleared (result is not negative)
     assert!(!cpu.get_negative_flag(), "Negative flag should be cleared");
 }
+
+#[test]
+fn test_adc_abs_x_page_cross_costs_a_cycle() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    // ADC $20F0,X with X = 0x20 reads $2110
+    memory[0xFFFC] = Opcode::AdcAbsX as u8;
+    memory[0xFFFD] = 0xF0;
+    memory[0xFFFE] = 0x20;
+    memory[0x2110] = 0x15;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_accumulator(0x20);
+    cpu.set_index_register_x(0x20);
+    let result = cpu.step(&mut memory).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x35, "Accumulator should be 0x35");
+    assert_eq!(result.cycles, 5, "Crossing a page should cost 5 cycles");
+}
+
+#[test]
+fn test_adc_abs_y() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::AdcAbsY as u8;
+    memory[0xFFFD] = 0x00;
+    memory[0xFFFE] = 0x20;
+    memory[0x2005] = 0x15;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_accumulator(0x20);
+    cpu.set_index_register_y(0x05);
+    let result = cpu.step(&mut memory).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x35, "Accumulator should be 0x35");
+    assert_eq!(result.cycles, 4);
+}
+
+#[test]
+fn test_adc_in_x() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    // ADC ($40,X) with X = 0x04, the pointer at $44/$45 = $2000
+    memory[0xFFFC] = Opcode::AdcInX as u8;
+    memory[0xFFFD] = 0x40;
+    memory[0x0044] = 0x00;
+    memory[0x0045] = 0x20;
+    memory[0x2000] = 0x15;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_accumulator(0x20);
+    cpu.set_index_register_x(0x04);
+    let result = cpu.step(&mut memory).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x35, "Accumulator should be 0x35");
+    assert_eq!(result.cycles, 6);
+}
+
+#[test]
+fn test_adc_in_y() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    // ADC ($40),Y with the pointer at $40/$41 = $20FF and Y = 0x01
+    memory[0xFFFC] = Opcode::AdcInY as u8;
+    memory[0xFFFD] = 0x40;
+    memory[0x0040] = 0xFF;
+    memory[0x0041] = 0x20;
+    memory[0x2100] = 0x15;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_accumulator(0x20);
+    cpu.set_index_register_y(0x01);
+    let result = cpu.step(&mut memory).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x35, "Accumulator should be 0x35");
+    assert_eq!(result.cycles, 6, "Crossing a page should cost 6 cycles");
+}
+
+#[test]
+fn test_lda_abs_x_page_cross() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    // LDA $20FF,X with X = 1 reads from $2100, crossing a page
+    memory[0xFFFC] = Opcode::LdaAbsX as u8;
+    memory[0xFFFD] = 0xFF;
+    memory[0xFFFE] = 0x20;
+    memory[0x2100] = 0x80;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_index_register_x(0x01);
+    cpu.execute(&mut memory, 5).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x80, "Accumulator should be 0x80");
+    assert_eq!(cpu.get_program_counter(), 0xFFFF);
+    assert!(!cpu.get_zero_flag(), "Zero flag should be cleared");
+    assert!(cpu.get_negative_flag(), "Negative flag should be set");
+}
+
+#[test]
+fn test_lda_in_x() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    // LDA ($20,X) with X = 4 reads the pointer from $24/$25
+    memory[0xFFFC] = Opcode::LdaInX as u8;
+    memory[0xFFFD] = 0x20;
+    memory[0x0024] = 0x00;
+    memory[0x0025] = 0x80;
+    memory[0x8000] = 0x37;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_index_register_x(0x04);
+    cpu.execute(&mut memory, 6).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x37, "Accumulator should be 0x37");
+    assert_eq!(cpu.get_program_counter(), 0xFFFE);
+}
+
+#[test]
+fn test_ldx_im() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::LdxIm as u8;
+    memory[0xFFFD] = 0x00;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_index_register_x(0x42);
+    cpu.execute(&mut memory, 2).unwrap();
+
+    assert_eq!(cpu.get_index_register_x(), 0x00, "X should be 0x00");
+    assert!(cpu.get_zero_flag(), "Zero flag should be set");
+    assert!(!cpu.get_negative_flag(), "Negative flag should be cleared");
+}
+
+#[test]
+fn test_ldx_zpy_wraps_in_zero_page() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    // LDX $F0,Y with Y = 0x20 wraps around to $10 instead of $0110
+    memory[0xFFFC] = Opcode::LdxZpy as u8;
+    memory[0xFFFD] = 0xF0;
+    memory[0x0010] = 0x42;
+    memory[0x0110] = 0x99;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_index_register_y(0x20);
+    cpu.execute(&mut memory, 4).unwrap();
+
+    assert_eq!(cpu.get_index_register_x(), 0x42, "X should be 0x42");
+}
+
+#[test]
+fn test_lda_zpx_wraps_in_zero_page() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    // LDA $FF,X with X = 0x01 reads $00
+    memory[0xFFFC] = Opcode::LdaZpx as u8;
+    memory[0xFFFD] = 0xFF;
+    memory[0x0000] = 0x42;
+    memory[0x0100] = 0x99;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_index_register_x(0x01);
+    cpu.execute(&mut memory, 4).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x42, "Accumulator should be 0x42");
+}
+
+#[test]
+fn test_lda_abs_x_wraps_past_ffff() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    // LDA $FFF0,X with X = 0x20 reads $0010
+    memory[0x0200] = Opcode::LdaAbsX as u8;
+    memory[0x0201] = 0xF0;
+    memory[0x0202] = 0xFF;
+    memory[0x0010] = 0x42;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_program_counter(0x0200);
+    cpu.set_index_register_x(0x20);
+    cpu.execute(&mut memory, 5).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x42, "Accumulator should be 0x42");
+}
+
+#[test]
+fn test_lda_in_x_pointer_wraps_in_zero_page() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    // LDA ($FE,X) with X = 0x01: the pointer sits at $FF and $00
+    memory[0xFFFC] = Opcode::LdaInX as u8;
+    memory[0xFFFD] = 0xFE;
+    memory[0x00FF] = 0x00;
+    memory[0x0000] = 0x30;
+    memory[0x0100] = 0x40;
+    memory[0x3000] = 0x42;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_index_register_x(0x01);
+    cpu.execute(&mut memory, 6).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x42, "Accumulator should be 0x42");
+}
+
+#[test]
+fn test_lda_in_y_pointer_wraps_in_zero_page() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    // LDA ($FF),Y: the pointer high byte comes from $00, not $0100
+    memory[0xFFFC] = Opcode::LdaInY as u8;
+    memory[0xFFFD] = 0xFF;
+    memory[0x00FF] = 0x00;
+    memory[0x0000] = 0x30;
+    memory[0x0100] = 0x40;
+    memory[0x3005] = 0x42;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_index_register_y(0x05);
+    cpu.execute(&mut memory, 5).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x42, "Accumulator should be 0x42");
+}
+
+#[test]
+fn test_program_counter_wraps_at_ffff() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    // LDA # at $FFFF takes its operand from $0000
+    memory[0xFFFF] = Opcode::LdaIm as u8;
+    memory[0x0000] = 0x42;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_program_counter(0xFFFF);
+    cpu.execute(&mut memory, 2).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x42, "Accumulator should be 0x42");
+    assert_eq!(cpu.get_program_counter(), 0x0001);
+}
+
+#[test]
+fn test_jmp_abs_operand_wraps_at_ffff() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    // JMP at $FFFE has its low byte at $FFFF and its high byte at $0000
+    memory[0xFFFE] = Opcode::JmpAbs as u8;
+    memory[0xFFFF] = 0x34;
+    memory[0x0000] = 0x12;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_program_counter(0xFFFE);
+    cpu.execute(&mut memory, 3).unwrap();
+
+    assert_eq!(cpu.get_program_counter(), 0x1234);
+}
+
+#[test]
+fn test_ldy_abs() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::LdyAbs as u8;
+    memory[0xFFFD] = 0x34;
+    memory[0xFFFE] = 0x12;
+    memory[0x1234] = 0xF0;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.execute(&mut memory, 4).unwrap();
+
+    assert_eq!(cpu.get_index_register_y(), 0xF0, "Y should be 0xF0");
+    assert!(!cpu.get_zero_flag(), "Zero flag should be cleared");
+    assert!(cpu.get_negative_flag(), "Negative flag should be set");
+}
+
+#[test]
+fn test_sta_zp() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::StaZp as u8;
+    memory[0xFFFD] = 0x10;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_accumulator(0x42);
+    cpu.execute(&mut memory, 3).unwrap();
+
+    assert_eq!(memory[0x0010], 0x42, "Memory at 0x0010 should be 0x42");
+    assert_eq!(cpu.get_program_counter(), 0xFFFE);
+}
+
+#[test]
+fn test_sta_abs_x_always_takes_five_cycles() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    // no page is crossed, but stores pay the indexing cycle anyway
+    memory[0xFFFC] = Opcode::StaAbsX as u8;
+    memory[0xFFFD] = 0x00;
+    memory[0xFFFE] = 0x20;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_accumulator(0x42);
+    cpu.set_index_register_x(0x05);
+    cpu.execute(&mut memory, 5).unwrap();
+
+    assert_eq!(memory[0x2005], 0x42, "Memory at 0x2005 should be 0x42");
+    assert_eq!(cpu.get_program_counter(), 0xFFFF);
+}
+
+#[test]
+fn test_sta_in_y() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    // STA ($40),Y with the pointer at $40/$41 = $3000 and Y = 0x10
+    memory[0xFFFC] = Opcode::StaInY as u8;
+    memory[0xFFFD] = 0x40;
+    memory[0x0040] = 0x00;
+    memory[0x0041] = 0x30;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_accumulator(0x42);
+    cpu.set_index_register_y(0x10);
+    cpu.execute(&mut memory, 6).unwrap();
+
+    assert_eq!(memory[0x3010], 0x42, "Memory at 0x3010 should be 0x42");
+    assert_eq!(cpu.get_program_counter(), 0xFFFE);
+}
+
+#[test]
+fn test_stx_abs() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::StxAbs as u8;
+    memory[0xFFFD] = 0x00;
+    memory[0xFFFE] = 0x40;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_index_register_x(0x80);
+    cpu.execute(&mut memory, 4).unwrap();
+
+    assert_eq!(memory[0x4000], 0x80, "Memory at 0x4000 should be 0x80");
+    assert!(
+        !cpu.get_negative_flag(),
+        "Stores should not touch the flags"
+    );
+}
+
+#[test]
+fn test_sty_zpx() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::StyZpx as u8;
+    memory[0xFFFD] = 0x10;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_index_register_x(0x05);
+    cpu.set_index_register_y(0x42);
+    cpu.execute(&mut memory, 4).unwrap();
+
+    assert_eq!(memory[0x0015], 0x42, "Memory at 0x0015 should be 0x42");
+}
+
+#[test]
+fn test_tax() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::Tax as u8;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_accumulator(0x80);
+    cpu.execute(&mut memory, 2).unwrap();
+
+    assert_eq!(cpu.get_index_register_x(), 0x80, "X should be 0x80");
+    assert!(!cpu.get_zero_flag(), "Zero flag should be cleared");
+    assert!(cpu.get_negative_flag(), "Negative flag should be set");
+}
+
+#[test]
+fn test_tya() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::Tya as u8;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_accumulator(0x42);
+    cpu.set_index_register_y(0x00);
+    cpu.execute(&mut memory, 2).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x00, "Accumulator should be 0x00");
+    assert!(cpu.get_zero_flag(), "Zero flag should be set");
+}
+
+#[test]
+fn test_txs_does_not_touch_flags() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::Txs as u8;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_index_register_x(0x00);
+    cpu.execute(&mut memory, 2).unwrap();
+
+    assert_eq!(cpu.get_stack_register(), 0x00);
+    assert!(!cpu.get_zero_flag(), "Zero flag should be untouched");
+}
+
+#[test]
+fn test_tsx() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::Tsx as u8;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.execute(&mut memory, 2).unwrap();
+
+    assert_eq!(cpu.get_index_register_x(), cpu.get_stack_register());
+    assert!(cpu.get_negative_flag(), "Negative flag should be set");
+}
+
+#[test]
+fn test_pha_pla() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    // PHA, LDA #$00, PLA
+    memory[0x0200] = Opcode::Pha as u8;
+    memory[0x0201] = Opcode::LdaIm as u8;
+    memory[0x0202] = 0x00;
+    memory[0x0203] = Opcode::Pla as u8;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_program_counter(0x0200);
+    let stack_start = cpu.get_stack_register();
+    cpu.set_accumulator(0x99);
+    cpu.execute(&mut memory, 3 + 2 + 4).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x99, "Accumulator should be 0x99");
+    assert!(!cpu.get_zero_flag(), "PLA should update the zero flag");
+    assert!(
+        cpu.get_negative_flag(),
+        "PLA should update the negative flag"
+    );
+    assert_eq!(cpu.get_stack_register(), stack_start);
+}
+
+#[test]
+fn test_php_sets_break_and_unused_bits() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::Php as u8;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_carry_flag(true);
+    cpu.execute(&mut memory, 3).unwrap();
+
+    let pushed = memory[0x0100 + cpu.get_stack_register() as usize + 1];
+    assert_eq!(
+        pushed, 0b0011_0001,
+        "Pushed status should have C, B and bit 5"
+    );
+    assert!(
+        !cpu.get_break_command_flag(),
+        "PHP should not change the live flags"
+    );
+}
+
+#[test]
+fn test_plp_ignores_break_and_unused_bits() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::Plp as u8;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    memory[0x0100 + cpu.get_stack_register().wrapping_add(1) as usize] = 0xFF;
+    cpu.execute(&mut memory, 4).unwrap();
+
+    assert!(cpu.get_carry_flag(), "Carry flag should be set");
+    assert!(cpu.get_zero_flag(), "Zero flag should be set");
+    assert!(cpu.get_interrupt_disable_flag(), "I flag should be set");
+    assert!(cpu.get_decimal_flag(), "Decimal flag should be set");
+    assert!(cpu.get_overflow_flag(), "Overflow flag should be set");
+    assert!(cpu.get_negative_flag(), "Negative flag should be set");
+    assert!(
+        !cpu.get_break_command_flag(),
+        "Break flag should not be pulled"
+    );
+}
+
+#[test]
+fn test_jsr_rts_round_trip() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    // JSR $3000, LDX #$01 at the return address, subroutine is LDA #$42, RTS
+    memory[0x0200] = Opcode::Jsr as u8;
+    memory[0x0201] = 0x00;
+    memory[0x0202] = 0x30;
+    memory[0x0203] = Opcode::LdxIm as u8;
+    memory[0x0204] = 0x01;
+    memory[0x3000] = Opcode::LdaIm as u8;
+    memory[0x3001] = 0x42;
+    memory[0x3002] = Opcode::Rts as u8;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_program_counter(0x0200);
+    let stack_start = cpu.get_stack_register();
+    cpu.execute(&mut memory, 6 + 2 + 6 + 2).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x42, "Subroutine should have run");
+    assert_eq!(cpu.get_index_register_x(), 0x01, "Caller should resume");
+    assert_eq!(cpu.get_program_counter(), 0x0205);
+    assert_eq!(cpu.get_stack_register(), stack_start);
+}
+
+#[test]
+fn test_rti() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::Rti as u8;
+
+    // stack holds status, then the return address $1234
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    let sp = 0x0100 + cpu.get_stack_register() as usize;
+    memory[sp - 2] = 0b1000_0001;
+    memory[sp - 1] = 0x34;
+    memory[sp] = 0x12;
+    cpu.set_stack_register(cpu.get_stack_register() - 3);
+    cpu.execute(&mut memory, 6).unwrap();
+
+    assert_eq!(cpu.get_program_counter(), 0x1234, "RTI should not add one");
+    assert!(cpu.get_carry_flag(), "Carry flag should be pulled");
+    assert!(cpu.get_negative_flag(), "Negative flag should be pulled");
+    assert_eq!(0x0100 + cpu.get_stack_register() as usize, sp);
+}
+
+#[test]
+fn test_jmp_abs() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::JmpAbs as u8;
+    memory[0xFFFD] = 0x00;
+    memory[0xFFFE] = 0x40;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.execute(&mut memory, 3).unwrap();
+
+    assert_eq!(cpu.get_program_counter(), 0x4000);
+}
+
+#[test]
+fn test_jmp_ind() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::JmpInd as u8;
+    memory[0xFFFD] = 0x20;
+    memory[0xFFFE] = 0x01;
+    memory[0x0120] = 0xFC;
+    memory[0x0121] = 0xBA;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.execute(&mut memory, 5).unwrap();
+
+    assert_eq!(cpu.get_program_counter(), 0xBAFC);
+}
+
+#[test]
+fn test_jmp_ind_page_boundary_bug() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    // JMP ($30FF) takes the high byte from $3000, not $3100
+    memory[0xFFFC] = Opcode::JmpInd as u8;
+    memory[0xFFFD] = 0xFF;
+    memory[0xFFFE] = 0x30;
+    memory[0x30FF] = 0x80;
+    memory[0x3000] = 0x50;
+    memory[0x3100] = 0x40;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.execute(&mut memory, 5).unwrap();
+
+    assert_eq!(cpu.get_program_counter(), 0x5080);
+}
+
+#[test]
+fn test_beq_not_taken() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::Beq as u8;
+    memory[0xFFFD] = 0x10;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_zero_flag(false);
+    cpu.execute(&mut memory, 2).unwrap();
+
+    assert_eq!(cpu.get_program_counter(), 0xFFFE);
+}
+
+#[test]
+fn test_bne_taken_forward() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0x0200] = Opcode::Bne as u8;
+    memory[0x0201] = 0x10;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_program_counter(0x0200);
+    cpu.execute(&mut memory, 3).unwrap();
+
+    assert_eq!(cpu.get_program_counter(), 0x0212);
+}
+
+#[test]
+fn test_bcs_taken_backward_across_page() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    // from $0302 an offset of -4 lands on $02FE, a different page
+    memory[0x0300] = Opcode::Bcs as u8;
+    memory[0x0301] = 0xFC;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_program_counter(0x0300);
+    cpu.set_carry_flag(true);
+    cpu.execute(&mut memory, 4).unwrap();
+
+    assert_eq!(cpu.get_program_counter(), 0x02FE);
+}
+
+#[test]
+fn test_bmi_bvs_follow_flags() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    // BMI is not taken, BVS skips two bytes forward
+    memory[0x0200] = Opcode::Bmi as u8;
+    memory[0x0201] = 0x40;
+    memory[0x0202] = Opcode::Bvs as u8;
+    memory[0x0203] = 0x02;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_program_counter(0x0200);
+    cpu.set_overflow_flag(true);
+    cpu.execute(&mut memory, 2 + 3).unwrap();
+
+    assert_eq!(cpu.get_program_counter(), 0x0206);
+}
+
+#[test]
+fn test_countdown_loop() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    // walk a linked list in a table until it reaches zero
+    //   $0200 LDX #$00
+    //   $0202 LDA $0300,X
+    //   $0205 TAX
+    //   $0206 BNE $0202
+    // the table at $0300 chains 0 -> 2 -> 1 -> 0
+    memory[0x0200] = Opcode::LdxIm as u8;
+    memory[0x0201] = 0x00;
+    memory[0x0202] = Opcode::LdaAbsX as u8;
+    memory[0x0203] = 0x00;
+    memory[0x0204] = 0x03;
+    memory[0x0205] = Opcode::Tax as u8;
+    memory[0x0206] = Opcode::Bne as u8;
+    memory[0x0207] = 0xFA;
+    memory[0x0300] = 0x02;
+    memory[0x0301] = 0x00;
+    memory[0x0302] = 0x01;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_program_counter(0x0200);
+    // LDX, then two taken passes and one that falls through
+    cpu.execute(&mut memory, 2 + 2 * (4 + 2 + 3) + (4 + 2 + 2))
+        .unwrap();
+
+    assert_eq!(cpu.get_index_register_x(), 0x00);
+    assert_eq!(cpu.get_program_counter(), 0x0208);
+}
+
+#[test]
+fn test_sbc_im() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::SbcIm as u8;
+    memory[0xFFFD] = 0x10;
+
+    // carry set means no borrow
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_accumulator(0x30);
+    cpu.set_carry_flag(true);
+    cpu.execute(&mut memory, 2).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x20, "Accumulator should be 0x20");
+    assert!(
+        cpu.get_carry_flag(),
+        "Carry flag should stay set (no borrow)"
+    );
+    assert!(!cpu.get_overflow_flag(), "Overflow flag should be cleared");
+}
+
+#[test]
+fn test_sbc_borrow_in() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::SbcIm as u8;
+    memory[0xFFFD] = 0x10;
+
+    // carry clear subtracts one more
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_accumulator(0x30);
+    cpu.set_carry_flag(false);
+    cpu.execute(&mut memory, 2).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x1F, "Accumulator should be 0x1F");
+    assert!(cpu.get_carry_flag(), "Carry flag should be set (no borrow)");
+}
+
+#[test]
+fn test_sbc_borrow_out() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::SbcZp as u8;
+    memory[0xFFFD] = 0x10;
+    memory[0x0010] = 0x01;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_accumulator(0x00);
+    cpu.set_carry_flag(true);
+    cpu.execute(&mut memory, 3).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0xFF, "Accumulator should be 0xFF");
+    assert!(
+        !cpu.get_carry_flag(),
+        "Carry flag should be cleared (borrow)"
+    );
+    assert!(cpu.get_negative_flag(), "Negative flag should be set");
+    assert!(!cpu.get_zero_flag(), "Zero flag should be cleared");
+}
+
+#[test]
+fn test_sbc_overflow() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    // -128 - 1 overflows to +127
+    memory[0xFFFC] = Opcode::SbcAbs as u8;
+    memory[0xFFFD] = 0x00;
+    memory[0xFFFE] = 0x20;
+    memory[0x2000] = 0x01;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_accumulator(0x80);
+    cpu.set_carry_flag(true);
+    cpu.execute(&mut memory, 4).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x7F, "Accumulator should be 0x7F");
+    assert!(cpu.get_overflow_flag(), "Overflow flag should be set");
+    assert!(cpu.get_carry_flag(), "Carry flag should be set");
+}
+
+#[test]
+fn test_sbc_in_y_page_cross() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::SbcInY as u8;
+    memory[0xFFFD] = 0x40;
+    memory[0x0040] = 0xF0;
+    memory[0x0041] = 0x20;
+    memory[0x2100] = 0x05;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_accumulator(0x05);
+    cpu.set_index_register_y(0x10);
+    cpu.set_carry_flag(true);
+    cpu.execute(&mut memory, 6).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x00, "Accumulator should be 0x00");
+    assert!(cpu.get_zero_flag(), "Zero flag should be set");
+    assert_eq!(cpu.get_program_counter(), 0xFFFE);
+}
+
+#[test]
+fn test_sbc_im_alias() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = 0xEB;
+    memory[0xFFFD] = 0x01;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_undocumented_opcodes(true);
+    cpu.set_accumulator(0x10);
+    cpu.set_carry_flag(true);
+    cpu.execute(&mut memory, 2).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x0F, "Accumulator should be 0x0F");
+}
+
+#[test]
+fn test_sbc_im_alias_is_invalid_when_strict() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = 0xEB;
+    memory[0xFFFD] = 0x01;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+
+    assert_eq!(
+        cpu.execute(&mut memory, 2),
+        Err(CpuError::InvalidOpcode {
+            opcode: 0xEB,
+            program_counter: 0xFFFC,
+        })
+    );
+}
+
+// runs a single immediate-mode ADC or SBC with the decimal flag set and
+// returns (A, N, V, Z, C)
+fn run_decimal(opcode: Opcode, a: u8, operand: u8, carry: bool) -> (u8, bool, bool, bool, bool) {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = opcode as u8;
+    memory[0xFFFD] = operand;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_decimal_flag(true);
+    cpu.set_accumulator(a);
+    cpu.set_carry_flag(carry);
+    cpu.execute(&mut memory, 2).unwrap();
+
+    (
+        cpu.get_accumulator(),
+        cpu.get_negative_flag(),
+        cpu.get_overflow_flag(),
+        cpu.get_zero_flag(),
+        cpu.get_carry_flag(),
+    )
+}
+
+#[test]
+fn test_adc_decimal_nmos_flags() {
+    // examples from appendix A of Bruce Clark's decimal mode tutorial,
+    // including invalid BCD operands: (A, M, C) -> (A, N, V, Z, C)
+    let cases = [
+        ((0x00, 0x00, false), (0x00, false, false, true, false)),
+        ((0x79, 0x00, true), (0x80, true, true, false, false)),
+        ((0x24, 0x56, false), (0x80, true, true, false, false)),
+        ((0x93, 0x82, false), (0x75, false, true, false, true)),
+        ((0x89, 0x76, false), (0x65, false, false, false, true)),
+        ((0x89, 0x76, true), (0x66, false, false, true, true)),
+        ((0x80, 0xF0, false), (0xD0, false, true, false, true)),
+        ((0x80, 0xFA, false), (0xE0, true, false, false, true)),
+        ((0x2F, 0x4F, false), (0x74, false, false, false, false)),
+        ((0x6F, 0x00, true), (0x76, false, false, false, false)),
+    ];
+
+    for ((a, m, c), expected) in cases {
+        assert_eq!(
+            run_decimal(Opcode::AdcIm, a, m, c),
+            expected,
+            "ADC {a:02X} + {m:02X} + {c}"
+        );
+    }
+}
+
+#[test]
+fn test_sbc_decimal_nmos_flags() {
+    let cases = [
+        ((0x00, 0x00, false), (0x99, true, false, false, false)),
+        ((0x00, 0x00, true), (0x00, false, false, true, true)),
+        ((0x00, 0x01, true), (0x99, true, false, false, false)),
+        ((0x0A, 0x00, true), (0x0A, false, false, false, true)),
+        ((0x0B, 0x00, false), (0x0A, false, false, false, true)),
+        ((0x9A, 0x00, true), (0x9A, true, false, false, true)),
+        ((0x9B, 0x00, false), (0x9A, true, false, false, true)),
+    ];
+
+    for ((a, m, c), expected) in cases {
+        assert_eq!(
+            run_decimal(Opcode::SbcIm, a, m, c),
+            expected,
+            "SBC {a:02X} - {m:02X} - !{c}"
+        );
+    }
+}
+
+#[test]
+fn test_decimal_mode_valid_bcd_exhaustive() {
+    let to_bcd = |n: u32| (((n / 10) << 4) | (n % 10)) as u8;
+
+    for a in 0..100 {
+        for m in 0..100 {
+            for carry in [false, true] {
+                let c = carry as u32;
+
+                let (result, _, _, _, carry_out) =
+                    run_decimal(Opcode::AdcIm, to_bcd(a), to_bcd(m), carry);
+                assert_eq!(result, to_bcd((a + m + c) % 100), "ADC {a} + {m} + {c}");
+                assert_eq!(carry_out, a + m + c >= 100, "ADC carry {a} + {m} + {c}");
+
+                let (result, _, _, _, carry_out) =
+                    run_decimal(Opcode::SbcIm, to_bcd(a), to_bcd(m), carry);
+                let difference = (a + 100 + c) - m - 1;
+                assert_eq!(result, to_bcd(difference % 100), "SBC {a} - {m} - !{c}");
+                assert_eq!(carry_out, difference >= 100, "SBC borrow {a} - {m} - !{c}");
+            }
+        }
+    }
+}
+
+#[test]
+fn test_and_im() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::AndIm as u8;
+    memory[0xFFFD] = 0x0F;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_accumulator(0xF0);
+    cpu.execute(&mut memory, 2).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x00, "Accumulator should be 0x00");
+    assert!(cpu.get_zero_flag(), "Zero flag should be set");
+    assert!(!cpu.get_negative_flag(), "Negative flag should be cleared");
+}
+
+#[test]
+fn test_ora_abs_y_page_cross() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::OraAbsY as u8;
+    memory[0xFFFD] = 0xF0;
+    memory[0xFFFE] = 0x20;
+    memory[0x2110] = 0x80;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_accumulator(0x01);
+    cpu.set_index_register_y(0x20);
+    cpu.execute(&mut memory, 5).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x81, "Accumulator should be 0x81");
+    assert!(cpu.get_negative_flag(), "Negative flag should be set");
+    assert_eq!(cpu.get_program_counter(), 0xFFFF);
+}
+
+#[test]
+fn test_eor_in_x() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::EorInX as u8;
+    memory[0xFFFD] = 0x10;
+    memory[0x0012] = 0x00;
+    memory[0x0013] = 0x30;
+    memory[0x3000] = 0xFF;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_accumulator(0x0F);
+    cpu.set_index_register_x(0x02);
+    cpu.execute(&mut memory, 6).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0xF0, "Accumulator should be 0xF0");
+    assert!(cpu.get_negative_flag(), "Negative flag should be set");
+}
+
+#[test]
+fn test_bit_zp() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::BitZp as u8;
+    memory[0xFFFD] = 0x10;
+    memory[0x0010] = 0b1100_0000;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_accumulator(0x01);
+    cpu.execute(&mut memory, 3).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x01, "BIT should not change A");
+    assert!(cpu.get_zero_flag(), "Zero flag should be set");
+    assert!(cpu.get_negative_flag(), "Negative flag should be bit 7");
+    assert!(cpu.get_overflow_flag(), "Overflow flag should be bit 6");
+}
+
+#[test]
+fn test_bit_abs() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::BitAbs as u8;
+    memory[0xFFFD] = 0x00;
+    memory[0xFFFE] = 0x20;
+    memory[0x2000] = 0b0000_0011;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_accumulator(0x01);
+    cpu.set_negative_flag(true);
+    cpu.set_overflow_flag(true);
+    cpu.execute(&mut memory, 4).unwrap();
+
+    assert!(!cpu.get_zero_flag(), "Zero flag should be cleared");
+    assert!(!cpu.get_negative_flag(), "Negative flag should be bit 7");
+    assert!(!cpu.get_overflow_flag(), "Overflow flag should be bit 6");
+}
+
+#[test]
+fn test_cmp_im_equal() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::CmpIm as u8;
+    memory[0xFFFD] = 0x42;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_accumulator(0x42);
+    cpu.execute(&mut memory, 2).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x42, "CMP should not change A");
+    assert!(cpu.get_carry_flag(), "Carry flag should be set");
+    assert!(cpu.get_zero_flag(), "Zero flag should be set");
+    assert!(!cpu.get_negative_flag(), "Negative flag should be cleared");
+}
+
+#[test]
+fn test_cmp_abs_x_less_than() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::CmpAbsX as u8;
+    memory[0xFFFD] = 0x00;
+    memory[0xFFFE] = 0x20;
+    memory[0x2001] = 0x20;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_accumulator(0x10);
+    cpu.set_index_register_x(0x01);
+    cpu.set_carry_flag(true);
+    cpu.execute(&mut memory, 4).unwrap();
+
+    assert!(!cpu.get_carry_flag(), "Carry flag should be cleared");
+    assert!(!cpu.get_zero_flag(), "Zero flag should be cleared");
+    assert!(cpu.get_negative_flag(), "Negative flag should be set");
+}
+
+#[test]
+fn test_cpx_zp_greater_than() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::CpxZp as u8;
+    memory[0xFFFD] = 0x10;
+    memory[0x0010] = 0x01;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_index_register_x(0x80);
+    cpu.execute(&mut memory, 3).unwrap();
+
+    assert!(cpu.get_carry_flag(), "Carry flag should be set");
+    assert!(!cpu.get_zero_flag(), "Zero flag should be cleared");
+    assert!(!cpu.get_negative_flag(), "Negative flag should be cleared");
+}
+
+#[test]
+fn test_cpy_abs() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::CpyAbs as u8;
+    memory[0xFFFD] = 0x00;
+    memory[0xFFFE] = 0x20;
+    memory[0x2000] = 0x05;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_index_register_y(0x05);
+    cpu.execute(&mut memory, 4).unwrap();
+
+    assert!(cpu.get_carry_flag(), "Carry flag should be set");
+    assert!(cpu.get_zero_flag(), "Zero flag should be set");
+}
+
+#[test]
+fn test_inx_wraps() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::Inx as u8;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_index_register_x(0xFF);
+    cpu.execute(&mut memory, 2).unwrap();
+
+    assert_eq!(cpu.get_index_register_x(), 0x00, "X should wrap to 0x00");
+    assert!(cpu.get_zero_flag(), "Zero flag should be set");
+}
+
+#[test]
+fn test_dey() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::Dey as u8;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_index_register_y(0x00);
+    cpu.execute(&mut memory, 2).unwrap();
+
+    assert_eq!(cpu.get_index_register_y(), 0xFF, "Y should wrap to 0xFF");
+    assert!(cpu.get_negative_flag(), "Negative flag should be set");
+}
+
+#[test]
+fn test_inc_zp() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::IncZp as u8;
+    memory[0xFFFD] = 0x10;
+    memory[0x0010] = 0x7F;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.execute(&mut memory, 5).unwrap();
+
+    assert_eq!(memory[0x0010], 0x80, "Memory at 0x0010 should be 0x80");
+    assert!(cpu.get_negative_flag(), "Negative flag should be set");
+    assert_eq!(cpu.get_program_counter(), 0xFFFE);
+}
+
+#[test]
+fn test_dec_abs_x_always_takes_seven_cycles() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::DecAbsX as u8;
+    memory[0xFFFD] = 0x00;
+    memory[0xFFFE] = 0x20;
+    memory[0x2001] = 0x01;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_index_register_x(0x01);
+    cpu.execute(&mut memory, 7).unwrap();
+
+    assert_eq!(memory[0x2001], 0x00, "Memory at 0x2001 should be 0x00");
+    assert!(cpu.get_zero_flag(), "Zero flag should be set");
+    assert_eq!(cpu.get_program_counter(), 0xFFFF);
+}
+
+#[test]
+fn test_asl_acc() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::AslAcc as u8;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_accumulator(0b1100_0001);
+    cpu.execute(&mut memory, 2).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0b1000_0010);
+    assert!(cpu.get_carry_flag(), "Carry flag should get bit 7");
+    assert!(cpu.get_negative_flag(), "Negative flag should be set");
+}
+
+#[test]
+fn test_lsr_zpx() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::LsrZpx as u8;
+    memory[0xFFFD] = 0x10;
+    memory[0x0011] = 0b0000_0001;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_index_register_x(0x01);
+    cpu.execute(&mut memory, 6).unwrap();
+
+    assert_eq!(memory[0x0011], 0x00, "Memory at 0x0011 should be 0x00");
+    assert!(cpu.get_carry_flag(), "Carry flag should get bit 0");
+    assert!(cpu.get_zero_flag(), "Zero flag should be set");
+}
+
+#[test]
+fn test_rol_abs() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::RolAbs as u8;
+    memory[0xFFFD] = 0x00;
+    memory[0xFFFE] = 0x20;
+    memory[0x2000] = 0b1000_0000;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_carry_flag(true);
+    cpu.execute(&mut memory, 6).unwrap();
+
+    assert_eq!(
+        memory[0x2000], 0b0000_0001,
+        "Carry should rotate into bit 0"
+    );
+    assert!(cpu.get_carry_flag(), "Carry flag should get bit 7");
+}
+
+#[test]
+fn test_ror_acc() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::RorAcc as u8;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_accumulator(0b0000_0010);
+    cpu.set_carry_flag(true);
+    cpu.execute(&mut memory, 2).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0b1000_0001);
+    assert!(!cpu.get_carry_flag(), "Carry flag should get bit 0");
+    assert!(cpu.get_negative_flag(), "Negative flag should be set");
+}
+
+#[test]
+fn test_flag_instructions() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0x0200] = Opcode::Sec as u8;
+    memory[0x0201] = Opcode::Sei as u8;
+    memory[0x0202] = Opcode::Sed as u8;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_program_counter(0x0200);
+    cpu.execute(&mut memory, 6).unwrap();
+
+    assert!(cpu.get_carry_flag(), "Carry flag should be set");
+    assert!(cpu.get_interrupt_disable_flag(), "I flag should be set");
+    assert!(cpu.get_decimal_flag(), "Decimal flag should be set");
+
+    memory[0x0203] = Opcode::Clc as u8;
+    memory[0x0204] = Opcode::Cli as u8;
+    memory[0x0205] = Opcode::Cld as u8;
+    memory[0x0206] = Opcode::Clv as u8;
+
+    cpu.set_overflow_flag(true);
+    cpu.execute(&mut memory, 8).unwrap();
+
+    assert!(!cpu.get_carry_flag(), "Carry flag should be cleared");
+    assert!(
+        !cpu.get_interrupt_disable_flag(),
+        "I flag should be cleared"
+    );
+    assert!(!cpu.get_decimal_flag(), "Decimal flag should be cleared");
+    assert!(!cpu.get_overflow_flag(), "Overflow flag should be cleared");
+    assert_eq!(cpu.get_program_counter(), 0x0207);
+}
+
+#[test]
+fn test_nop() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::Nop as u8;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    let flags_before = cpu.get_flags().into_bytes();
+    cpu.execute(&mut memory, 2).unwrap();
+
+    assert_eq!(cpu.get_program_counter(), 0xFFFD);
+    assert_eq!(cpu.get_flags().into_bytes(), flags_before);
+    assert_eq!(cpu.get_accumulator(), 0x00);
+}
+
+#[test]
+fn test_brk() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0x0200] = Opcode::Brk as u8;
+    memory[0xFFFE] = 0x00;
+    memory[0xFFFF] = 0x80;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_program_counter(0x0200);
+    cpu.set_carry_flag(true);
+    let sp = 0x0100 + cpu.get_stack_register() as usize;
+    cpu.execute(&mut memory, 7).unwrap();
+
+    assert_eq!(cpu.get_program_counter(), 0x8000, "PC should be the vector");
+    assert!(cpu.get_interrupt_disable_flag(), "I flag should be set");
+    assert!(!cpu.get_break_command_flag(), "B only exists on the stack");
+
+    // return address is PC + 2, status has B and bit 5 set
+    assert_eq!(memory[sp], 0x02);
+    assert_eq!(memory[sp - 1], 0x02);
+    assert_eq!(memory[sp - 2], 0b0011_0001);
+    assert_eq!(0x0100 + cpu.get_stack_register() as usize, sp - 3);
+}
+
+#[test]
+fn test_brk_rti_round_trip() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    // BRK, padding byte, LDA #$42; the handler is just RTI
+    memory[0x0200] = Opcode::Brk as u8;
+    memory[0x0201] = 0xFF;
+    memory[0x0202] = Opcode::LdaIm as u8;
+    memory[0x0203] = 0x42;
+    memory[0x8000] = Opcode::Rti as u8;
+    memory[0xFFFE] = 0x00;
+    memory[0xFFFF] = 0x80;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_program_counter(0x0200);
+    cpu.execute(&mut memory, 7 + 6 + 2).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x42);
+    assert_eq!(cpu.get_program_counter(), 0x0204);
+    assert!(!cpu.get_interrupt_disable_flag(), "RTI should restore I");
+}
+
+#[test]
+fn test_irq_taken_after_current_instruction() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0x0200] = Opcode::Nop as u8;
+    memory[0xFFFE] = 0x00;
+    memory[0xFFFF] = 0x90;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_program_counter(0x0200);
+    cpu.set_irq(true);
+    cpu.execute(&mut memory, 2 + 7).unwrap();
+
+    assert_eq!(cpu.get_program_counter(), 0x9000, "IRQ should be taken");
+    assert!(cpu.get_interrupt_disable_flag(), "I flag should be set");
+
+    // the pushed status has B clear and the return address is after the NOP
+    let sp = 0x0100 + cpu.get_stack_register() as usize;
+    assert_eq!(memory[sp + 1], 0b0010_0000);
+    assert_eq!(memory[sp + 2], 0x01);
+    assert_eq!(memory[sp + 3], 0x02);
+}
+
+#[test]
+fn test_irq_masked_by_interrupt_disable() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0x0200] = Opcode::Nop as u8;
+    memory[0x0201] = Opcode::Nop as u8;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_program_counter(0x0200);
+    cpu.set_interrupt_disable_flag(true);
+    cpu.set_irq(true);
+    cpu.execute(&mut memory, 4).unwrap();
+
+    assert_eq!(cpu.get_program_counter(), 0x0202, "IRQ should be ignored");
+}
+
+#[test]
+fn test_nmi_is_edge_triggered() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    // the NMI handler is a string of NOPs
+    memory[0x0200] = Opcode::Nop as u8;
+    for addr in 0x9000..0x9004 {
+        memory[addr] = Opcode::Nop as u8;
+    }
+    memory[0xFFFA] = 0x00;
+    memory[0xFFFB] = 0x90;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_program_counter(0x0200);
+    cpu.set_interrupt_disable_flag(true);
+    cpu.set_nmi(true);
+    cpu.execute(&mut memory, 2 + 7).unwrap();
+
+    assert_eq!(cpu.get_program_counter(), 0x9000, "NMI ignores the I flag");
+
+    // holding the line does not raise another NMI
+    cpu.execute(&mut memory, 4).unwrap();
+    assert_eq!(cpu.get_program_counter(), 0x9002);
+
+    // but releasing and asserting it again does
+    cpu.set_nmi(false);
+    cpu.set_nmi(true);
+    cpu.execute(&mut memory, 2 + 7).unwrap();
+    assert_eq!(cpu.get_program_counter(), 0x9000);
+}
+
+#[test]
+fn test_nmi_hijacks_brk() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0x0200] = Opcode::Brk as u8;
+    memory[0xFFFA] = 0x00;
+    memory[0xFFFB] = 0x90;
+    memory[0xFFFE] = 0x00;
+    memory[0xFFFF] = 0x80;
+
+    // the NMI edge lands while BRK is already under way
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_program_counter(0x0200);
+    let sp = 0x0100 + cpu.get_stack_register() as usize;
+    cpu.set_nmi(true);
+    cpu.execute(&mut memory, 7).unwrap();
+
+    assert_eq!(
+        cpu.get_program_counter(),
+        0x9000,
+        "BRK should use the NMI vector"
+    );
+    assert_eq!(
+        memory[sp - 2] & 0b0001_0000,
+        0b0001_0000,
+        "B is still pushed"
+    );
+
+    // and the NMI is not taken a second time
+    memory[0x9000] = Opcode::Nop as u8;
+    cpu.execute(&mut memory, 2).unwrap();
+    assert_eq!(cpu.get_program_counter(), 0x9001);
+}
+
+#[test]
+fn test_reset_loads_pc_from_vector() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = 0x00;
+    memory[0xFFFD] = 0x80;
+    memory[0x8000] = Opcode::LdaIm as u8;
+    memory[0x8001] = 0x42;
+
+    // seven cycles of reset, then LDA #$42 from $8000
+    cpu.reset();
+    cpu.execute(&mut memory, 7 + 2).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x42, "Accumulator should be 0x42");
+    assert_eq!(cpu.get_program_counter(), 0x8002);
+}
+
+#[test]
+fn test_reset_sequence_state() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = 0x34;
+    memory[0xFFFD] = 0x12;
+
+    cpu.reset();
+    let sp = cpu.get_stack_register();
+    cpu.execute(&mut memory, 7).unwrap();
+
+    assert_eq!(cpu.get_program_counter(), 0x1234, "PC should be the vector");
+    assert!(cpu.get_interrupt_disable_flag(), "I flag should be set");
+    assert_eq!(cpu.get_stack_register(), sp.wrapping_sub(3));
+    assert_eq!(
+        cpu.get_stack_register(),
+        0xFD,
+        "Power-on SP of 0 ends at 0xFD"
+    );
+    assert_eq!(
+        memory[0x0100 + sp as usize],
+        0x00,
+        "Reset should not write the stack"
+    );
+}
+
+#[test]
+fn test_reset_keeps_registers_and_flags() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = 0x00;
+    memory[0xFFFD] = 0x80;
+
+    cpu.set_accumulator(0x42);
+    cpu.set_index_register_x(0x13);
+    cpu.set_carry_flag(true);
+    cpu.set_decimal_flag(true);
+    cpu.reset();
+    cpu.execute(&mut memory, 7).unwrap();
+
+    assert_eq!(
+        cpu.get_accumulator(),
+        0x42,
+        "Accumulator should survive reset"
+    );
+    assert_eq!(cpu.get_index_register_x(), 0x13, "X should survive reset");
+    assert!(cpu.get_carry_flag(), "Carry flag should survive reset");
+    assert!(cpu.get_decimal_flag(), "NMOS reset should leave D alone");
+}
+
+#[test]
+fn test_cmos_reset_clears_decimal() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::new(Variant::Cmos65C02);
+
+    cpu.set_decimal_flag(true);
+    cpu.reset();
+    cpu.execute(&mut memory, 7).unwrap();
+
+    assert!(!cpu.get_decimal_flag(), "Decimal flag should be cleared");
+}
+
+#[test]
+fn test_reset_does_not_take_pending_interrupts() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = 0x00;
+    memory[0xFFFD] = 0x80;
+    memory[0x8000] = Opcode::Nop as u8;
+    memory[0x8001] = Opcode::Nop as u8;
+
+    // reset sets I, so a held IRQ line is ignored afterwards
+    cpu.reset();
+    cpu.set_irq(true);
+    cpu.execute(&mut memory, 7 + 2 + 2).unwrap();
+
+    assert_eq!(cpu.get_program_counter(), 0x8002);
+}
+
+#[test]
+fn test_stack_lives_in_page_one() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::Pha as u8;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_accumulator(0x42);
+    cpu.execute(&mut memory, 3).unwrap();
+
+    assert_eq!(memory[0x01FF], 0x42, "PHA should write to $01FF");
+    assert_eq!(memory[0x00FF], 0x00, "Zero page should be untouched");
+    assert_eq!(cpu.get_stack_register(), 0xFE);
+}
+
+#[test]
+fn test_stack_pointer_wraps_on_overflow() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::Pha as u8;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_stack_register(0x00);
+    cpu.set_accumulator(0x42);
+    cpu.execute(&mut memory, 3).unwrap();
+
+    assert_eq!(memory[0x0100], 0x42, "PHA should write to $0100");
+    assert_eq!(cpu.get_stack_register(), 0xFF, "SP should wrap to 0xFF");
+}
+
+#[test]
+fn test_stack_pointer_wraps_on_underflow() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::Pla as u8;
+    memory[0x0100] = 0x42;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_stack_register(0xFF);
+    cpu.execute(&mut memory, 4).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x42, "PLA should read from $0100");
+    assert_eq!(cpu.get_stack_register(), 0x00, "SP should wrap to 0x00");
+}
+
+#[test]
+fn test_invalid_opcode_halts_by_default() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0x0200] = Opcode::Nop as u8;
+    memory[0x0201] = 0x02;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_program_counter(0x0200);
+    let result = cpu.execute(&mut memory, 10);
+
+    assert_eq!(
+        result,
+        Err(CpuError::InvalidOpcode {
+            opcode: 0x02,
+            program_counter: 0x0201,
+        })
+    );
+    assert_eq!(
+        cpu.get_program_counter(),
+        0x0201,
+        "PC should stay on the bad byte"
+    );
+}
+
+#[test]
+fn test_invalid_opcode_as_nop() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0x0200] = 0x02;
+    memory[0x0201] = Opcode::LdaIm as u8;
+    memory[0x0202] = 0x42;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_program_counter(0x0200);
+    cpu.set_invalid_opcode_policy(InvalidOpcodePolicy::Nop);
+    cpu.execute(&mut memory, 2 + 2).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x42);
+    assert_eq!(cpu.get_program_counter(), 0x0203);
+}
+
+#[test]
+fn test_invalid_opcode_trap() {
+    use std::sync::{Arc, Mutex};
+
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    // the trap lets $02 through and stops on $12
+    memory[0x0200] = 0x02;
+    memory[0x0201] = 0x12;
+
+    let trapped = Arc::new(Mutex::new(Vec::new()));
+    let log = Arc::clone(&trapped);
+    cpu.set_invalid_opcode_policy(InvalidOpcodePolicy::Trap(Box::new(move |error| {
+        log.lock().unwrap().push(error);
+        match error {
+            CpuError::InvalidOpcode { opcode: 0x02, .. } => Ok(()),
+            _ => Err(error),
+        }
+    })));
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_program_counter(0x0200);
+    let result = cpu.execute(&mut memory, 10);
+
+    let stop = CpuError::InvalidOpcode {
+        opcode: 0x12,
+        program_counter: 0x0201,
+    };
+    assert_eq!(result, Err(stop));
+    assert_eq!(trapped.lock().unwrap().len(), 2);
+    assert_eq!(trapped.lock().unwrap()[1], stop);
+}
+
+#[test]
+fn test_cpu_is_send() {
+    // the trap callback is boxed as Send, so a CPU can still move to another
+    // thread whatever its invalid opcode policy
+    fn need_send<T: Send>() {}
+    need_send::<CPU>();
+}
+
+#[test]
+fn test_step_reports_cycles_and_opcode() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = 0x00;
+    memory[0xFFFD] = 0x80;
+    memory[0x8000] = Opcode::LdaAbsX as u8;
+    memory[0x8001] = 0xFF;
+    memory[0x8002] = 0x20;
+
+    cpu.reset();
+    let reset = cpu.step(&mut memory).unwrap();
+    assert_eq!(
+        reset,
+        StepResult {
+            cycles: 7,
+            opcode: None,
+            interrupt: Some(Interrupt::Reset),
+            halted: None,
+        }
+    );
+
+    cpu.set_index_register_x(0x01);
+    let load = cpu.step(&mut memory).unwrap();
+    assert_eq!(
+        load,
+        StepResult {
+            cycles: 5,
+            opcode: Some(Opcode::LdaAbsX as u8),
+            interrupt: None,
+            halted: None,
+        }
+    );
+    assert_eq!(cpu.get_program_counter(), 0x8003);
+}
+
+#[test]
+fn test_step_reports_interrupts() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0x0200] = Opcode::Nop as u8;
+    memory[0x0201] = Opcode::Brk as u8;
+    memory[0xFFFA] = 0x00;
+    memory[0xFFFB] = 0x90;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_program_counter(0x0200);
+    cpu.set_nmi(true);
+
+    // the NOP finishes first, then the NMI sequence runs on its own step
+    assert_eq!(cpu.step(&mut memory).unwrap().interrupt, None);
+    let nmi = cpu.step(&mut memory).unwrap();
+    assert_eq!(nmi.cycles, 7);
+    assert_eq!(nmi.opcode, None);
+    assert_eq!(nmi.interrupt, Some(Interrupt::Nmi));
+
+    // a BRK hijacked by NMI still reports its opcode
+    cpu.set_nmi(false);
+    cpu.set_program_counter(0x0201);
+    cpu.set_nmi(true);
+    let brk = cpu.step(&mut memory).unwrap();
+    assert_eq!(brk.opcode, Some(Opcode::Brk as u8));
+    assert_eq!(brk.interrupt, Some(Interrupt::Nmi));
+    assert_eq!(cpu.get_program_counter(), 0x9000);
+}
+
+#[test]
+fn test_execute_reports_overrun() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = Opcode::LdaAbs as u8;
+    memory[0xFFFD] = 0x00;
+    memory[0xFFFE] = 0x20;
+
+    // a one-cycle budget still runs the whole four-cycle instruction
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    let overrun = cpu.execute(&mut memory, 1).unwrap();
+
+    assert_eq!(overrun, 3);
+    assert_eq!(cpu.get_program_counter(), 0xFFFF);
+}
+
+#[test]
+fn test_execute_exact_budget_has_no_overrun() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0x0200] = Opcode::Nop as u8;
+    memory[0x0201] = Opcode::Nop as u8;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_program_counter(0x0200);
+
+    assert_eq!(cpu.execute(&mut memory, 4).unwrap(), 0);
+    assert_eq!(cpu.get_program_counter(), 0x0202);
+}
+
+// over two billion NOPs, so it only runs on request:
+// cargo test --release -- --ignored test_execute_budget_near_u32_max
+#[test]
+#[ignore = "slow, run with --release --ignored"]
+fn test_execute_budget_near_u32_max() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    for addr in 0..=0xFFFF {
+        memory[addr] = Opcode::Nop as u8;
+    }
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+
+    // the last NOP carries the count from u32::MAX - 1 past u32::MAX
+    assert_eq!(cpu.execute(&mut memory, u32::MAX), Ok(1));
+    assert_eq!(cpu.get_total_cycles(), u32::MAX as u64 + 1);
+}
+
+#[test]
+fn test_total_cycles_accumulate_across_calls() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0xFFFC] = 0x00;
+    memory[0xFFFD] = 0x80;
+    memory[0x8000] = Opcode::LdaAbs as u8;
+    memory[0x8001] = 0x00;
+    memory[0x8002] = 0x20;
+    memory[0x8003] = Opcode::Nop as u8;
+
+    assert_eq!(cpu.get_total_cycles(), 0);
+
+    cpu.reset();
+    cpu.execute(&mut memory, 7).unwrap();
+    assert_eq!(cpu.get_total_cycles(), 7);
+
+    // the overrun is counted too
+    cpu.execute(&mut memory, 1).unwrap();
+    assert_eq!(cpu.get_total_cycles(), 11);
+
+    cpu.step(&mut memory).unwrap();
+    assert_eq!(cpu.get_total_cycles(), 13);
+
+    // reset does not rewind the clock
+    cpu.reset();
+    cpu.execute(&mut memory, 7).unwrap();
+    assert_eq!(cpu.get_total_cycles(), 20);
+}
+
+#[test]
+fn test_snapshot_restore() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0x0200] = Opcode::LdaIm as u8;
+    memory[0x0201] = 0x42;
+    memory[0x0202] = Opcode::Pha as u8;
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_program_counter(0x0200);
+    cpu.execute(&mut memory, 2).unwrap();
+    let snapshot = cpu.snapshot();
+
+    cpu.execute(&mut memory, 3).unwrap();
+    cpu.set_carry_flag(true);
+    assert_eq!(cpu.get_total_cycles(), 5);
+
+    cpu.restore(&snapshot);
+    assert_eq!(cpu.get_program_counter(), 0x0202);
+    assert_eq!(cpu.get_accumulator(), 0x42);
+    assert_eq!(cpu.get_stack_register(), 0xFF);
+    assert!(!cpu.get_carry_flag());
+    assert_eq!(cpu.get_total_cycles(), 2, "Cycle count should be restored");
+}
@@ -0,0 +1,81 @@
+use cpu6052::*;
+
+#[test]
+fn test_decode_lda_abs_x() {
+    let instruction = decode(Opcode::LdaAbsX as u8).expect("LDA abs,X should decode");
+
+    assert_eq!(instruction.mnemonic, Mnemonic::Lda);
+    assert_eq!(instruction.mode, AddressingMode::AbsoluteX);
+    assert_eq!(instruction.cycles, 4);
+    assert!(
+        instruction.page_penalty,
+        "Indexed reads should pay for a page cross"
+    );
+    assert_eq!(instruction.size(), 3);
+}
+
+#[test]
+fn test_decode_stores_never_pay_page_penalty() {
+    for opcode in [Opcode::StaAbsX, Opcode::StaAbsY, Opcode::StaInY] {
+        let instruction = decode(opcode as u8).unwrap();
+        assert!(
+            !instruction.page_penalty,
+            "{opcode:?} should not pay for a page cross"
+        );
+    }
+}
+
+#[test]
+fn test_decode_jam_is_undocumented() {
+    let instruction = decode(0x02).unwrap();
+
+    assert_eq!(instruction.mnemonic, Mnemonic::Jam);
+    assert!(
+        instruction.undocumented,
+        "$02 is not a documented instruction"
+    );
+}
+
+#[test]
+fn test_decode_table_covers_every_nmos_byte() {
+    assert!(DECODE_TABLE.iter().all(|entry| entry.is_some()));
+}
+
+#[test]
+fn test_decode_table_has_every_documented_opcode() {
+    let count = DECODE_TABLE
+        .iter()
+        .flatten()
+        .filter(|instruction| !instruction.undocumented)
+        .count();
+    assert_eq!(count, 151);
+}
+
+#[test]
+fn test_encode_round_trips_through_decode() {
+    for opcode in 0..=255u8 {
+        if let Some(instruction) = decode(opcode) {
+            let encoded = encode(instruction.mnemonic, instruction.mode).unwrap();
+            let decoded = decode(encoded).unwrap();
+            assert_eq!(decoded.mnemonic, instruction.mnemonic);
+            assert_eq!(decoded.mode, instruction.mode);
+        }
+    }
+
+    assert_eq!(
+        encode(Mnemonic::Sbc, AddressingMode::Immediate),
+        Some(Opcode::SbcIm as u8),
+        "The documented opcode should win over the alias"
+    );
+    assert_eq!(
+        encode(Mnemonic::Nop, AddressingMode::Implied),
+        Some(Opcode::Nop as u8),
+        "$EA should win over the undocumented NOPs"
+    );
+    assert_eq!(encode(Mnemonic::Jmp, AddressingMode::ZeroPage), None);
+}
+
+#[test]
+fn test_mnemonic_display() {
+    assert_eq!(Mnemonic::Lda.to_string(), "LDA");
+}
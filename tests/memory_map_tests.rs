@@ -0,0 +1,229 @@
+use cpu6052::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+// a device with four registers that logs the offsets it is accessed at
+struct LoggingDevice {
+    registers: [u8; 4],
+    log: Rc<RefCell<Vec<u16>>>,
+}
+
+impl Bus for LoggingDevice {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.log.borrow_mut().push(addr);
+        self.registers[addr as usize]
+    }
+
+    fn write(&mut self, addr: u16, value: u8) {
+        self.log.borrow_mut().push(addr);
+        self.registers[addr as usize] = value;
+    }
+
+    fn peek(&self, addr: u16) -> u8 {
+        self.registers[addr as usize]
+    }
+}
+
+fn logging_device() -> (LoggingDevice, Rc<RefCell<Vec<u16>>>) {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let device = LoggingDevice {
+        registers: [0; 4],
+        log: Rc::clone(&log),
+    };
+    (device, log)
+}
+
+#[test]
+fn test_memory_map_routes_regions() {
+    let (device, log) = logging_device();
+
+    let mut rom = vec![0xEA; 0x4000];
+    rom[0x0000] = 0x11;
+    let mut map = MemoryMap::builder()
+        .ram(0x0000..=0x7FFF)
+        .device_mirrored(0x8000..=0x80FF, 4, device)
+        .rom(0xC000..=0xFFFF, rom)
+        .build()
+        .unwrap();
+
+    map.write(0x1234, 0x42);
+    assert_eq!(map.read(0x1234), 0x42, "RAM should be writable");
+
+    map.write(0x8001, 0x99);
+    assert_eq!(
+        map.read(0x8005),
+        0x99,
+        "Device registers repeat every 4 bytes"
+    );
+    assert_eq!(*log.borrow(), vec![1, 1], "Device should see local offsets");
+
+    assert_eq!(map.read(0xC000), 0x11);
+    map.write(0xC000, 0x00);
+    assert_eq!(map.read(0xC000), 0x11, "ROM should ignore writes");
+
+    assert_eq!(map.read(0xA000), 0x00, "Unmapped reads return 0");
+}
+
+#[test]
+fn test_memory_map_mirrors_rom_and_ram() {
+    let mut rom = vec![0x00; 0x2000];
+    rom[0x0010] = 0x42;
+    let mut map = MemoryMap::builder()
+        .ram_mirrored(0x0000..=0x1FFF, 0x0800)
+        .rom(0xC000..=0xFFFF, rom)
+        .build()
+        .unwrap();
+
+    assert_eq!(map.read(0xC010), 0x42);
+    assert_eq!(map.read(0xE010), 0x42, "8 KiB ROM should repeat in 16 KiB");
+
+    map.write(0x0001, 0x37);
+    assert_eq!(map.read(0x0801), 0x37);
+    assert_eq!(map.read(0x1801), 0x37);
+}
+
+#[test]
+fn test_memory_map_peek_has_no_side_effects() {
+    let (device, log) = logging_device();
+    let map = MemoryMap::builder()
+        .device(0xD000..=0xD003, device)
+        .build()
+        .unwrap();
+
+    assert_eq!(map.peek(0xD002), 0x00);
+    assert!(
+        log.borrow().is_empty(),
+        "Peek should not reach the device log"
+    );
+}
+
+#[test]
+fn test_memory_map_rejects_overlap() {
+    let result = MemoryMap::builder()
+        .ram(0x0000..=0x7FFF)
+        .rom(0x7000..=0x7FFF, vec![0; 0x1000])
+        .build();
+
+    assert_eq!(
+        result.err(),
+        Some(MemoryMapError::Overlap {
+            first: 0x0000..=0x7FFF,
+            second: 0x7000..=0x7FFF,
+        })
+    );
+}
+
+#[test]
+fn test_memory_map_rejects_bad_mirror() {
+    let result = MemoryMap::builder()
+        .rom(0xC000..=0xFFFF, vec![0; 0x3000])
+        .build();
+
+    assert_eq!(
+        result.err(),
+        Some(MemoryMapError::InvalidMirror {
+            range: 0xC000..=0xFFFF,
+            size: 0x3000,
+        })
+    );
+}
+
+#[test]
+fn test_memory_map_rejects_oversized_ram_mirror() {
+    let result = MemoryMap::builder()
+        .ram_mirrored(0x0000..=0x00FF, usize::MAX)
+        .build();
+
+    assert_eq!(
+        result.err(),
+        Some(MemoryMapError::InvalidMirror {
+            range: 0x0000..=0x00FF,
+            size: usize::MAX,
+        })
+    );
+}
+
+#[test]
+fn test_memory_map_from_mem() {
+    let mut memory = Mem::new();
+    memory.write_word(0xFFFC, 0x8000);
+    memory[0x8000] = Opcode::LdaIm as u8;
+    memory[0x8001] = 0x42;
+    memory[0x8002] = Opcode::StaAbs as u8;
+    memory[0x8003] = 0x00;
+    memory[0x8004] = 0x02;
+
+    let mut map = MemoryMap::from(memory);
+    let mut cpu = CPU::default();
+    cpu.reset();
+    cpu.execute(&mut map, 7 + 2 + 4).unwrap();
+
+    assert_eq!(map.peek(0x0200), 0x42);
+}
+
+#[test]
+fn test_cpu_boots_from_mapped_rom() {
+    // LDA #$42; STA $0010; JMP $C005 -- reset vector points at $C000
+    let mut rom = vec![0xEA; 0x4000];
+    rom[..8].copy_from_slice(&[0xA9, 0x42, 0x85, 0x10, 0xEA, 0x4C, 0x05, 0xC0]);
+    rom[0x3FFC] = 0x00;
+    rom[0x3FFD] = 0xC0;
+
+    let mut map = MemoryMap::builder()
+        .ram(0x0000..=0x07FF)
+        .rom(0xC000..=0xFFFF, rom)
+        .build()
+        .unwrap();
+    let mut cpu = CPU::default();
+    cpu.reset();
+    cpu.execute(&mut map, 7 + 2 + 3 + 2 + 3).unwrap();
+
+    assert_eq!(map.peek(0x0010), 0x42);
+    assert_eq!(cpu.get_program_counter(), 0xC005);
+}
+
+// a timer that only learns the time from `sync`
+struct Timer {
+    now: Rc<RefCell<u64>>,
+}
+
+impl Bus for Timer {
+    fn read(&mut self, _addr: u16) -> u8 {
+        *self.now.borrow() as u8
+    }
+
+    fn write(&mut self, _addr: u16, _value: u8) {}
+
+    fn peek(&self, _addr: u16) -> u8 {
+        *self.now.borrow() as u8
+    }
+
+    fn sync(&mut self, total_cycles: u64) {
+        *self.now.borrow_mut() = total_cycles;
+    }
+}
+
+#[test]
+fn test_memory_map_forwards_sync_to_devices() {
+    let now = Rc::new(RefCell::new(0));
+    let mut rom = vec![Opcode::Nop as u8; 0x100];
+    rom[0xFC] = 0x00;
+    rom[0xFD] = 0xFF;
+
+    let mut map = MemoryMap::builder()
+        .device(
+            0xD000..=0xD000,
+            Timer {
+                now: Rc::clone(&now),
+            },
+        )
+        .rom(0xFF00..=0xFFFF, rom)
+        .build()
+        .unwrap();
+    let mut cpu = CPU::default();
+    cpu.reset();
+    cpu.execute(&mut map, 7 + 2 + 2).unwrap();
+
+    assert_eq!(*now.borrow(), 11);
+    assert_eq!(*now.borrow(), cpu.get_total_cycles());
+}
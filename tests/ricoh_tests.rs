@@ -0,0 +1,87 @@
+mod common;
+
+use common::{opcode, setup_variant};
+use cpu6052::*;
+
+// loads `program` at $0200 on a 2A03 with D set
+fn setup(program: &[u8]) -> (Mem, CPU) {
+    let (memory, mut cpu) = setup_variant(Variant::Ricoh2A03, program);
+    cpu.set_decimal_flag(true);
+    (memory, cpu)
+}
+
+#[test]
+fn test_adc_ignores_decimal_flag() {
+    let (mut memory, mut cpu) = setup(&[Opcode::AdcIm as u8, 0x01]);
+    cpu.set_accumulator(0x09);
+
+    let result = cpu.step(&mut memory).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x0A, "Accumulator should be 0x0A");
+    assert!(!cpu.get_carry_flag(), "Carry flag should be cleared");
+    assert!(cpu.get_decimal_flag(), "Decimal flag should still be set");
+    assert_eq!(result.cycles, 2);
+}
+
+#[test]
+fn test_sbc_ignores_decimal_flag() {
+    let (mut memory, mut cpu) = setup(&[Opcode::SbcIm as u8, 0x01]);
+    cpu.set_accumulator(0x10);
+    cpu.set_carry_flag(true);
+
+    cpu.step(&mut memory).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x0F, "Accumulator should be 0x0F");
+    assert!(cpu.get_carry_flag(), "Carry flag should be set, no borrow");
+}
+
+#[test]
+fn test_php_still_pushes_decimal_flag() {
+    let (mut memory, mut cpu) = setup(&[Opcode::Php as u8]);
+
+    cpu.step(&mut memory).unwrap();
+
+    assert_eq!(
+        memory[0x01FF] & 0b00001000,
+        0b00001000,
+        "Pushed D should be set"
+    );
+}
+
+#[test]
+fn test_undocumented_opcodes_follow_nmos() {
+    let lax = opcode(Variant::Ricoh2A03, Mnemonic::Lax, AddressingMode::ZeroPage);
+    assert_eq!(Variant::Ricoh2A03.decode(lax), decode(lax));
+
+    let (mut memory, mut cpu) = setup(&[lax, 0x10]);
+    assert_eq!(
+        cpu.step(&mut memory),
+        Err(CpuError::InvalidOpcode {
+            opcode: lax,
+            program_counter: 0x0200,
+        }),
+        "Undocumented opcodes should still be opt-in"
+    );
+
+    memory[0x0010] = 0x42;
+    cpu.set_undocumented_opcodes(true);
+    cpu.step(&mut memory).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x42, "Accumulator should be 0x42");
+    assert_eq!(cpu.get_index_register_x(), 0x42, "X should be 0x42");
+}
+
+#[test]
+fn test_arr_ignores_decimal_flag() {
+    let arr = opcode(Variant::Ricoh2A03, Mnemonic::Arr, AddressingMode::Immediate);
+    let (mut memory, mut cpu) = setup(&[arr, 0xFF]);
+    cpu.set_undocumented_opcodes(true);
+    cpu.set_accumulator(0xFF);
+
+    cpu.step(&mut memory).unwrap();
+
+    // the plain binary ARR: $FF ROR = $7F, C from bit 6, V from bit 6 ^ bit 5
+    assert_eq!(cpu.get_accumulator(), 0x7F, "Accumulator should be 0x7F");
+    assert!(cpu.get_carry_flag(), "Carry flag should be set");
+    assert!(!cpu.get_overflow_flag(), "Overflow flag should be cleared");
+}
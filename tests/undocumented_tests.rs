@@ -0,0 +1,441 @@
+mod common;
+
+use common::{opcode, setup_variant};
+use cpu6052::*;
+
+// loads `program` at $0200 on a CPU with undocumented opcodes turned on
+fn setup(program: &[u8]) -> (Mem, CPU) {
+    let (memory, mut cpu) = setup_variant(Variant::Nmos6502, program);
+    cpu.set_undocumented_opcodes(true);
+    (memory, cpu)
+}
+
+#[test]
+fn test_undocumented_opcodes_are_invalid_by_default() {
+    let mut memory = Mem::default();
+    let mut cpu = CPU::default();
+
+    memory[0x0200] = 0xA7; // LAX $10
+
+    cpu.set_legacy_reset(true);
+    cpu.reset();
+    cpu.set_program_counter(0x0200);
+    let result = cpu.step(&mut memory);
+
+    assert_eq!(
+        result,
+        Err(CpuError::InvalidOpcode {
+            opcode: 0xA7,
+            program_counter: 0x0200,
+        })
+    );
+}
+
+#[test]
+fn test_lax_zp() {
+    let (mut memory, mut cpu) = setup(&[
+        opcode(Variant::Nmos6502, Mnemonic::Lax, AddressingMode::ZeroPage),
+        0x10,
+    ]);
+    memory[0x0010] = 0x80;
+
+    let result = cpu.step(&mut memory).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x80, "Accumulator should be 0x80");
+    assert_eq!(cpu.get_index_register_x(), 0x80, "X should be 0x80");
+    assert!(cpu.get_negative_flag(), "Negative flag should be set");
+    assert_eq!(result.cycles, 3);
+}
+
+#[test]
+fn test_lax_abs_y_page_cross_costs_a_cycle() {
+    let (mut memory, mut cpu) = setup(&[
+        opcode(Variant::Nmos6502, Mnemonic::Lax, AddressingMode::AbsoluteY),
+        0xFF,
+        0x20,
+    ]);
+    memory[0x2100] = 0x42;
+    cpu.set_index_register_y(0x01);
+
+    let result = cpu.step(&mut memory).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x42, "Accumulator should be 0x42");
+    assert_eq!(result.cycles, 5);
+}
+
+#[test]
+fn test_sax_zp_stores_a_and_x_without_flags() {
+    let (mut memory, mut cpu) = setup(&[
+        opcode(Variant::Nmos6502, Mnemonic::Sax, AddressingMode::ZeroPage),
+        0x10,
+    ]);
+    cpu.set_accumulator(0xF0);
+    cpu.set_index_register_x(0x3C);
+
+    cpu.step(&mut memory).unwrap();
+
+    assert_eq!(memory[0x0010], 0x30, "Memory at 0x0010 should be 0x30");
+    assert!(!cpu.get_zero_flag(), "Zero flag should be untouched");
+    assert!(
+        !cpu.get_negative_flag(),
+        "Negative flag should be untouched"
+    );
+}
+
+#[test]
+fn test_dcp_decrements_then_compares() {
+    let (mut memory, mut cpu) = setup(&[
+        opcode(Variant::Nmos6502, Mnemonic::Dcp, AddressingMode::ZeroPage),
+        0x10,
+    ]);
+    memory[0x0010] = 0x43;
+    cpu.set_accumulator(0x42);
+
+    let result = cpu.step(&mut memory).unwrap();
+
+    assert_eq!(memory[0x0010], 0x42, "Memory at 0x0010 should be 0x42");
+    assert!(cpu.get_zero_flag(), "Zero flag should be set");
+    assert!(cpu.get_carry_flag(), "Carry flag should be set");
+    assert_eq!(result.cycles, 5);
+}
+
+#[test]
+fn test_isc_increments_then_subtracts() {
+    let (mut memory, mut cpu) = setup(&[
+        opcode(Variant::Nmos6502, Mnemonic::Isc, AddressingMode::ZeroPage),
+        0x10,
+    ]);
+    memory[0x0010] = 0x0F;
+    cpu.set_accumulator(0x50);
+    cpu.set_carry_flag(true);
+
+    cpu.step(&mut memory).unwrap();
+
+    assert_eq!(memory[0x0010], 0x10, "Memory at 0x0010 should be 0x10");
+    assert_eq!(cpu.get_accumulator(), 0x40, "Accumulator should be 0x40");
+    assert!(cpu.get_carry_flag(), "Carry flag should be set, no borrow");
+}
+
+#[test]
+fn test_slo_abs_y_always_takes_seven_cycles() {
+    let (mut memory, mut cpu) = setup(&[
+        opcode(Variant::Nmos6502, Mnemonic::Slo, AddressingMode::AbsoluteY),
+        0x00,
+        0x20,
+    ]);
+    memory[0x2001] = 0x81;
+    cpu.set_accumulator(0x01);
+    cpu.set_index_register_y(0x01);
+
+    let result = cpu.step(&mut memory).unwrap();
+
+    assert_eq!(memory[0x2001], 0x02, "Memory at 0x2001 should be 0x02");
+    assert_eq!(cpu.get_accumulator(), 0x03, "Accumulator should be 0x03");
+    assert!(
+        cpu.get_carry_flag(),
+        "Carry flag should come from the shift"
+    );
+    assert_eq!(result.cycles, 7);
+}
+
+#[test]
+fn test_rla_rotates_then_ands() {
+    let (mut memory, mut cpu) = setup(&[
+        opcode(Variant::Nmos6502, Mnemonic::Rla, AddressingMode::ZeroPage),
+        0x10,
+    ]);
+    memory[0x0010] = 0x80;
+    cpu.set_accumulator(0xFF);
+    cpu.set_carry_flag(true);
+
+    cpu.step(&mut memory).unwrap();
+
+    assert_eq!(memory[0x0010], 0x01, "Memory at 0x0010 should be 0x01");
+    assert_eq!(cpu.get_accumulator(), 0x01, "Accumulator should be 0x01");
+    assert!(
+        cpu.get_carry_flag(),
+        "Carry flag should come from the rotate"
+    );
+}
+
+#[test]
+fn test_sre_shifts_then_eors() {
+    let (mut memory, mut cpu) = setup(&[
+        opcode(Variant::Nmos6502, Mnemonic::Sre, AddressingMode::ZeroPage),
+        0x10,
+    ]);
+    memory[0x0010] = 0x03;
+    cpu.set_accumulator(0x01);
+
+    cpu.step(&mut memory).unwrap();
+
+    assert_eq!(memory[0x0010], 0x01, "Memory at 0x0010 should be 0x01");
+    assert_eq!(cpu.get_accumulator(), 0x00, "Accumulator should be 0x00");
+    assert!(cpu.get_zero_flag(), "Zero flag should be set");
+    assert!(
+        cpu.get_carry_flag(),
+        "Carry flag should come from the shift"
+    );
+}
+
+#[test]
+fn test_rra_feeds_rotate_carry_into_adc() {
+    let (mut memory, mut cpu) = setup(&[
+        opcode(Variant::Nmos6502, Mnemonic::Rra, AddressingMode::ZeroPage),
+        0x10,
+    ]);
+    memory[0x0010] = 0x03;
+    cpu.set_accumulator(0x10);
+
+    cpu.step(&mut memory).unwrap();
+
+    // $03 ROR = $01 with C set, then $10 + $01 + 1
+    assert_eq!(memory[0x0010], 0x01, "Memory at 0x0010 should be 0x01");
+    assert_eq!(cpu.get_accumulator(), 0x12, "Accumulator should be 0x12");
+    assert!(!cpu.get_carry_flag(), "Carry flag should be cleared");
+}
+
+#[test]
+fn test_anc_copies_negative_into_carry() {
+    let (mut memory, mut cpu) = setup(&[
+        opcode(Variant::Nmos6502, Mnemonic::Anc, AddressingMode::Immediate),
+        0x80,
+    ]);
+    cpu.set_accumulator(0xFF);
+
+    cpu.step(&mut memory).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x80, "Accumulator should be 0x80");
+    assert!(cpu.get_negative_flag(), "Negative flag should be set");
+    assert!(cpu.get_carry_flag(), "Carry flag should match N");
+}
+
+#[test]
+fn test_alr_ands_then_shifts() {
+    let (mut memory, mut cpu) = setup(&[
+        opcode(Variant::Nmos6502, Mnemonic::Alr, AddressingMode::Immediate),
+        0x03,
+    ]);
+    cpu.set_accumulator(0xFF);
+
+    cpu.step(&mut memory).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x01, "Accumulator should be 0x01");
+    assert!(
+        cpu.get_carry_flag(),
+        "Carry flag should come from the shift"
+    );
+}
+
+#[test]
+fn test_arr_binary_flags() {
+    let (mut memory, mut cpu) = setup(&[
+        opcode(Variant::Nmos6502, Mnemonic::Arr, AddressingMode::Immediate),
+        0xFF,
+    ]);
+    cpu.set_accumulator(0x40);
+    cpu.set_carry_flag(true);
+
+    cpu.step(&mut memory).unwrap();
+
+    // $40 ROR with C in = $A0: C from bit 6 = 0, V from bit 6 ^ bit 5 = 1
+    assert_eq!(cpu.get_accumulator(), 0xA0, "Accumulator should be 0xA0");
+    assert!(cpu.get_negative_flag(), "Negative flag should be set");
+    assert!(!cpu.get_carry_flag(), "Carry flag should be cleared");
+    assert!(cpu.get_overflow_flag(), "Overflow flag should be set");
+}
+
+#[test]
+fn test_arr_decimal_fixup() {
+    let (mut memory, mut cpu) = setup(&[
+        opcode(Variant::Nmos6502, Mnemonic::Arr, AddressingMode::Immediate),
+        0xFF,
+    ]);
+    cpu.set_accumulator(0xFF);
+    cpu.set_decimal_flag(true);
+
+    cpu.step(&mut memory).unwrap();
+
+    // $FF ROR = $7F, then both nibbles of $FF need the BCD fixup
+    assert_eq!(cpu.get_accumulator(), 0xD5, "Accumulator should be 0xD5");
+    assert!(cpu.get_carry_flag(), "Carry flag should be set");
+    assert!(
+        !cpu.get_negative_flag(),
+        "Negative flag should be the old carry"
+    );
+    assert!(!cpu.get_overflow_flag(), "Overflow flag should be cleared");
+}
+
+#[test]
+fn test_sbx_subtracts_from_a_and_x() {
+    let (mut memory, mut cpu) = setup(&[
+        opcode(Variant::Nmos6502, Mnemonic::Sbx, AddressingMode::Immediate),
+        0x10,
+    ]);
+    cpu.set_accumulator(0xF0);
+    cpu.set_index_register_x(0x3F);
+    cpu.set_decimal_flag(true);
+
+    cpu.step(&mut memory).unwrap();
+
+    assert_eq!(cpu.get_index_register_x(), 0x20, "X should be 0x20");
+    assert_eq!(
+        cpu.get_accumulator(),
+        0xF0,
+        "Accumulator should be unchanged"
+    );
+    assert!(cpu.get_carry_flag(), "Carry flag should be set, no borrow");
+}
+
+#[test]
+fn test_undocumented_nop_abs_x_skips_its_operand() {
+    let (mut memory, mut cpu) = setup(&[0x1C, 0xFF, 0x20]);
+    cpu.set_index_register_x(0x01);
+
+    let result = cpu.step(&mut memory).unwrap();
+
+    assert_eq!(cpu.get_program_counter(), 0x0203);
+    assert_eq!(result.cycles, 5, "Crossing a page should cost 5 cycles");
+}
+
+#[test]
+fn test_jam_halts_until_reset() {
+    let (mut memory, mut cpu) = setup(&[0x02, Opcode::LdaIm as u8, 0x42]);
+
+    let jam = cpu.step(&mut memory).unwrap();
+    let halted = Some(HaltState::Jammed {
+        program_counter: 0x0200,
+    });
+    assert_eq!(jam.opcode, Some(0x02));
+    assert_eq!(jam.halted, halted);
+    assert_eq!(cpu.get_halt_state(), halted);
+
+    // interrupts do not get it going again
+    cpu.set_nmi(true);
+    cpu.set_irq(true);
+    let idle = cpu.step(&mut memory).unwrap();
+    assert_eq!(
+        idle,
+        StepResult {
+            cycles: 1,
+            opcode: None,
+            interrupt: None,
+            halted,
+        }
+    );
+    cpu.execute(&mut memory, 10).unwrap();
+    assert_eq!(
+        cpu.get_program_counter(),
+        0x0200,
+        "PC should stay on the JAM"
+    );
+    assert_eq!(
+        cpu.get_accumulator(),
+        0x00,
+        "Nothing after the JAM should run"
+    );
+
+    cpu.reset();
+    assert_eq!(cpu.get_halt_state(), None, "Reset should clear the halt");
+}
+
+#[test]
+fn test_ane_uses_magic_constant() {
+    let (mut memory, mut cpu) = setup(&[
+        opcode(Variant::Nmos6502, Mnemonic::Ane, AddressingMode::Immediate),
+        0xFF,
+        opcode(Variant::Nmos6502, Mnemonic::Ane, AddressingMode::Immediate),
+        0xFF,
+    ]);
+
+    // (A | $EE) & X & M with A = 0x01, X = 0x0F
+    cpu.set_accumulator(0x01);
+    cpu.set_index_register_x(0x0F);
+    cpu.step(&mut memory).unwrap();
+    assert_eq!(cpu.get_accumulator(), 0x0F, "Accumulator should be 0x0F");
+
+    cpu.set_magic_constant(0x00);
+    cpu.set_accumulator(0x01);
+    cpu.step(&mut memory).unwrap();
+    assert_eq!(cpu.get_accumulator(), 0x01, "Accumulator should be 0x01");
+}
+
+#[test]
+fn test_lxa_loads_a_and_x() {
+    let (mut memory, mut cpu) = setup(&[
+        opcode(Variant::Nmos6502, Mnemonic::Lxa, AddressingMode::Immediate),
+        0x81,
+    ]);
+    cpu.set_magic_constant(0xFF);
+
+    cpu.step(&mut memory).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x81, "Accumulator should be 0x81");
+    assert_eq!(cpu.get_index_register_x(), 0x81, "X should be 0x81");
+    assert!(cpu.get_negative_flag(), "Negative flag should be set");
+}
+
+#[test]
+fn test_las_ands_memory_with_sp() {
+    let (mut memory, mut cpu) = setup(&[
+        opcode(Variant::Nmos6502, Mnemonic::Las, AddressingMode::AbsoluteY),
+        0x00,
+        0x20,
+    ]);
+    memory[0x2000] = 0x3C;
+    cpu.set_stack_register(0xF0);
+
+    cpu.step(&mut memory).unwrap();
+
+    assert_eq!(cpu.get_accumulator(), 0x30, "Accumulator should be 0x30");
+    assert_eq!(cpu.get_index_register_x(), 0x30, "X should be 0x30");
+    assert_eq!(cpu.get_stack_register(), 0x30, "SP should be 0x30");
+}
+
+#[test]
+fn test_shx_ands_with_high_byte_plus_one() {
+    let (mut memory, mut cpu) = setup(&[
+        opcode(Variant::Nmos6502, Mnemonic::Shx, AddressingMode::AbsoluteY),
+        0x00,
+        0x20,
+    ]);
+    cpu.set_index_register_x(0xFF);
+    cpu.set_index_register_y(0x05);
+
+    cpu.step(&mut memory).unwrap();
+
+    assert_eq!(memory[0x2005], 0x21, "Memory at 0x2005 should be X & 0x21");
+}
+
+#[test]
+fn test_shx_page_cross_replaces_high_byte() {
+    let (mut memory, mut cpu) = setup(&[
+        opcode(Variant::Nmos6502, Mnemonic::Shx, AddressingMode::AbsoluteY),
+        0xFF,
+        0x20,
+    ]);
+    cpu.set_index_register_x(0x13);
+    cpu.set_index_register_y(0x01);
+
+    cpu.step(&mut memory).unwrap();
+
+    // X & $21 = $01, which also becomes the high byte of $2100
+    assert_eq!(memory[0x0100], 0x01, "Memory at 0x0100 should be 0x01");
+    assert_eq!(memory[0x2100], 0x00, "Memory at 0x2100 should be untouched");
+}
+
+#[test]
+fn test_tas_sets_sp_and_stores() {
+    let (mut memory, mut cpu) = setup(&[
+        opcode(Variant::Nmos6502, Mnemonic::Tas, AddressingMode::AbsoluteY),
+        0x00,
+        0x7F,
+    ]);
+    cpu.set_accumulator(0xF3);
+    cpu.set_index_register_x(0x3F);
+
+    cpu.step(&mut memory).unwrap();
+
+    assert_eq!(cpu.get_stack_register(), 0x33, "SP should be A & X");
+    assert_eq!(memory[0x7F00], 0x00, "Memory at 0x7F00 should be SP & 0x80");
+}